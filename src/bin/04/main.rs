@@ -11,4 +11,10 @@ fn main() {
 
     println!("Strategy 1: {}", strategy_1(&guards));
     println!("Strategy 2: {}", strategy_2(&guards));
+
+    if let Some(dir) = std::env::args().nth(1) {
+        let dir = std::path::Path::new(&dir);
+        std::fs::write(dir.join("guards.csv"), guards_csv(&guards)).expect("Cannot write CSV");
+        std::fs::write(dir.join("naps.csv"), naps_csv(&guards)).expect("Cannot write CSV");
+    }
 }
@@ -7,9 +7,13 @@ fn main() {
     let input = content
         .trim_end()
         .split('\n')
-        .map(|data| data.parse().unwrap())
+        .map(|data| data.parse::<Pt2>().unwrap())
         .collect::<Vec<_>>();
 
-    println!("Largest area: {}", largest_area(&input));
-    println!("Region size: {}", region_size(&input, 10000));
+    println!("Largest area: {}", largest_area(&input).unwrap());
+    println!("Region size: {}", region_size(&input, 10000).unwrap());
+
+    if let Some(path) = std::env::args().nth(1) {
+        std::fs::write(path, render_ppm(&input, 10000)).expect("Cannot write image");
+    }
 }
@@ -9,6 +9,25 @@ fn main() {
         .split('\n')
         .collect::<Vec<_>>();
 
-    println!("Task order: {}", task_execution_order(&input));
-    println!("Task duration: {}", task_duration(&input, 5, 60));
+    println!("Task order: {}", task_execution_order(&input).unwrap());
+
+    let schedule = task_schedule(&input, 5, 60).unwrap();
+    println!("Task duration: {}", schedule.duration());
+    for (policy, duration) in task_policy_durations(&input, 5, 60).unwrap() {
+        println!("Task duration ({}): {}", policy, duration);
+    }
+
+    if let Some(dir) = std::env::args().nth(1) {
+        let dir = std::path::Path::new(&dir);
+        let write = |name: &str, contents: String| {
+            std::fs::write(dir.join(name), contents).expect("Cannot write schedule");
+        };
+        write("schedule.txt", schedule.to_gantt(10));
+        write("schedule.csv", schedule.to_csv());
+        write("schedule.json", schedule.to_json());
+        write(
+            "steps.dot",
+            task_graph_dot(&input, 60, DotHighlight::CriticalPath).unwrap(),
+        );
+    }
 }
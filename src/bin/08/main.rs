@@ -6,6 +6,6 @@ fn main() {
     let content = read_file("src/bin/08/input.txt").expect("Missing input data");
     let input = content.trim_end();
 
-    println!("Metadata sum: {}", metadata_sum(&input));
-    println!("Root value: {}", root_value(&input));
+    println!("Metadata sum: {}", metadata_sum(&input).unwrap());
+    println!("Root value: {}", root_value(&input).unwrap());
 }
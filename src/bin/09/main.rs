@@ -1,7 +1,11 @@
 extern crate aoc_2018;
 use aoc_2018::d09::*;
+use aoc_2018::read_file;
 
 fn main() {
-    println!("Winner's score: {}", max_score(424, 71144));
-    println!("Winner's score: {}", max_score(424, 100 * 71144));
+    let content = read_file("src/bin/09/input.txt").expect("Missing input data");
+    let input = content.trim_end().parse::<GameDescription>().unwrap();
+
+    println!("Winner's score: {}", input.max_score(1).unwrap());
+    println!("Winner's score: {}", input.max_score(100).unwrap());
 }
@@ -22,6 +22,10 @@ impl Guard {
         self.minutes_asleep
     }
 
+    pub fn naps(&self) -> &[u32] {
+        &self.naps
+    }
+
     pub fn most_asleep_minute(&self) -> u8 {
         self.most_asleep_minute_and_count().0
     }
@@ -43,36 +47,90 @@ impl Guard {
     }
 }
 
-pub fn read_data(lines: &[&str]) -> HashMap<u32, Guard> {
+pub struct Shift {
+    date: String,
+    guard_id: u32,
+    naps: Vec<(u8, u8)>,
+}
+
+impl Shift {
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    pub fn guard_id(&self) -> u32 {
+        self.guard_id
+    }
+
+    pub fn is_sleeping_at(&self, minute: u8) -> bool {
+        self.naps
+            .iter()
+            .any(|&(start, end)| minute >= start && minute < end)
+    }
+}
+
+pub fn read_shifts(lines: &[&str]) -> Vec<Shift> {
     let mut lines = Vec::from(lines);
     lines.sort_unstable();
 
-    let mut guards: HashMap<u32, Guard> = HashMap::new();
-
-    let mut dummy_guard = Guard {
-        id: 0,
-        minutes_asleep: 0,
-        naps: [0; 60],
-    };
-    let mut guard: &mut Guard = &mut dummy_guard;
+    let mut shifts: Vec<Shift> = Vec::new();
     let mut start: u8 = 0;
     for line in &lines {
         if line.ends_with(" shift") {
-            let id = line[26..]
+            let guard_id = line[26..]
                 .split_whitespace()
                 .next()
                 .unwrap()
                 .parse::<u32>()
                 .unwrap();
-            guard = guards.entry(id).or_insert_with(|| Guard {
-                id,
-                minutes_asleep: 0,
-                naps: [0; 60],
+            let date = if &line[12..14] == "23" {
+                next_day(&line[6..11])
+            } else {
+                line[6..11].to_owned()
+            };
+            shifts.push(Shift {
+                date,
+                guard_id,
+                naps: Vec::new(),
             });
         } else if line.ends_with(" asleep") {
             start = line[15..17].parse::<u8>().unwrap();
         } else if line.ends_with(" up") {
             let end = line[15..17].parse::<u8>().unwrap();
+            if let Some(shift) = shifts.last_mut() {
+                shift.naps.push((start, end));
+            }
+        }
+    }
+
+    shifts
+}
+
+fn next_day(date: &str) -> String {
+    // 1518 is no leap year
+    const DAYS_IN_MONTH: [u8; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+    let month = date[0..2].parse::<u8>().unwrap();
+    let day = date[3..5].parse::<u8>().unwrap();
+    let (month, day) = if day < DAYS_IN_MONTH[usize::from(month - 1)] {
+        (month, day + 1)
+    } else {
+        (month % 12 + 1, 1)
+    };
+    format!("{:02}-{:02}", month, day)
+}
+
+pub fn read_data(lines: &[&str]) -> HashMap<u32, Guard> {
+    let mut guards: HashMap<u32, Guard> = HashMap::new();
+
+    for shift in read_shifts(lines) {
+        let id = shift.guard_id;
+        let guard = guards.entry(id).or_insert_with(|| Guard {
+            id,
+            minutes_asleep: 0,
+            naps: [0; 60],
+        });
+        for &(start, end) in &shift.naps {
             guard.mark_asleep(start, end);
         }
     }
@@ -80,6 +138,129 @@ pub fn read_data(lines: &[&str]) -> HashMap<u32, Guard> {
     guards
 }
 
+pub fn timetable(shifts: &[Shift]) -> String {
+    let id_width = shifts
+        .iter()
+        .map(|shift| shift.guard_id.to_string().len() + 1)
+        .max()
+        .unwrap_or(0)
+        .max(2);
+    let tens: String = (0..60)
+        .map(|minute| char::from(b'0' + minute / 10))
+        .collect();
+    let ones: String = (0..60)
+        .map(|minute| char::from(b'0' + minute % 10))
+        .collect();
+
+    let mut lines = vec![
+        format!("Date   {:<width$}  Minute", "ID", width = id_width),
+        format!("       {:<width$}  {}", "", tens, width = id_width),
+        format!("       {:<width$}  {}", "", ones, width = id_width),
+    ];
+    lines.extend(shifts.iter().map(|shift| {
+        let minutes: String = (0..60)
+            .map(|minute| {
+                if shift.is_sleeping_at(minute) {
+                    '#'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        format!(
+            "{}  {:<width$}  {}",
+            shift.date,
+            format!("#{}", shift.guard_id),
+            minutes,
+            width = id_width
+        )
+    }));
+
+    lines.join("\n")
+}
+
+pub fn naps_heatmap_svg(guards: &[&Guard]) -> String {
+    const CELL: usize = 10;
+    const LABEL: usize = 60;
+
+    let mut guards = Vec::from(guards);
+    guards.sort_unstable_by_key(|guard| guard.id);
+    let max_count = guards
+        .iter()
+        .flat_map(|guard| guard.naps.iter())
+        .max()
+        .cloned()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        LABEL + 60 * CELL,
+        guards.len() * CELL
+    );
+    for (row, guard) in guards.iter().enumerate() {
+        let y = row * CELL;
+        svg += &format!(
+            "<text x=\"0\" y=\"{}\" font-size=\"{}\">#{}</text>\n",
+            y + CELL - 1,
+            CELL,
+            guard.id
+        );
+        for (minute, &count) in guard.naps.iter().enumerate() {
+            svg += &format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"red\" fill-opacity=\"{:.3}\"><title>#{} 00:{:02} {}</title></rect>\n",
+                LABEL + minute * CELL,
+                y,
+                CELL,
+                CELL,
+                f64::from(count) / f64::from(max_count),
+                guard.id,
+                minute,
+                count
+            );
+        }
+    }
+    svg += "</svg>\n";
+
+    svg
+}
+
+pub fn guards_csv(guards: &[&Guard]) -> String {
+    let mut guards = Vec::from(guards);
+    guards.sort_unstable_by_key(|guard| guard.id);
+
+    let mut csv = String::from("id,minutes_asleep,most_asleep_minute,most_asleep_count\n");
+    for guard in guards {
+        let (minute, count) = guard.most_asleep_minute_and_count();
+        csv += &format!(
+            "{},{},{},{}\n",
+            guard.id, guard.minutes_asleep, minute, count
+        );
+    }
+
+    csv
+}
+
+pub fn naps_csv(guards: &[&Guard]) -> String {
+    let mut guards = Vec::from(guards);
+    guards.sort_unstable_by_key(|guard| guard.id);
+
+    let mut csv = String::from("id");
+    for minute in 0..60 {
+        csv += &format!(",{}", minute);
+    }
+    csv.push('\n');
+    for guard in guards {
+        csv += &guard.id.to_string();
+        for count in guard.naps.iter() {
+            csv += &format!(",{}", count);
+        }
+        csv.push('\n');
+    }
+
+    csv
+}
+
 pub fn most_asleep_guard<'a>(guards: &[&'a Guard]) -> &'a Guard {
     guards
         .iter()
@@ -146,6 +327,88 @@ mod tests {
         assert_eq!((45, 3), guard.most_asleep_minute_and_count());
     }
 
+    #[test]
+    fn test_read_shifts() {
+        let shifts = read_shifts(DATA);
+        assert_eq!(5, shifts.len());
+        assert_eq!(
+            vec!["11-01", "11-02", "11-03", "11-04", "11-05"],
+            shifts.iter().map(|shift| shift.date()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![10, 99, 10, 99, 99],
+            shifts
+                .iter()
+                .map(|shift| shift.guard_id())
+                .collect::<Vec<_>>()
+        );
+        assert!(!shifts[0].is_sleeping_at(4));
+        assert!(shifts[0].is_sleeping_at(5));
+        assert!(shifts[0].is_sleeping_at(24));
+        assert!(!shifts[0].is_sleeping_at(25));
+    }
+
+    #[test]
+    fn test_next_day() {
+        assert_eq!("11-02", next_day("11-01"));
+        assert_eq!("03-01", next_day("02-28"));
+        assert_eq!("01-01", next_day("12-31"));
+    }
+
+    #[test]
+    fn test_timetable() {
+        assert_eq!(
+            timetable(&read_shifts(DATA)),
+            [
+                "Date   ID   Minute",
+                "            000000000011111111112222222222333333333344444444445555555555",
+                "            012345678901234567890123456789012345678901234567890123456789",
+                "11-01  #10  .....####################.....#########################.....",
+                "11-02  #99  ........................................##########..........",
+                "11-03  #10  ........................#####...............................",
+                "11-04  #99  ....................................##########..............",
+                "11-05  #99  .............................................##########.....",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_naps_heatmap_svg() {
+        let guards_data = read_data(DATA);
+        let guards: Vec<&Guard> = guards_data.values().collect();
+        let svg = naps_heatmap_svg(&guards);
+        assert!(svg.starts_with("<svg "));
+        assert_eq!(2 * 60, svg.matches("<rect ").count());
+        assert!(svg.contains("<title>#99 00:45 3</title>"));
+        assert!(svg.contains("fill-opacity=\"1.000\"><title>#99 00:45 3</title>"));
+    }
+
+    #[test]
+    fn test_guards_csv() {
+        let guards_data = read_data(DATA);
+        let guards: Vec<&Guard> = guards_data.values().collect();
+        assert_eq!(
+            guards_csv(&guards),
+            "id,minutes_asleep,most_asleep_minute,most_asleep_count\n10,50,24,2\n99,30,45,3\n"
+        );
+    }
+
+    #[test]
+    fn test_naps_csv() {
+        let guards_data = read_data(DATA);
+        let guards: Vec<&Guard> = guards_data.values().collect();
+        let csv = naps_csv(&guards);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!(61, lines[0].split(',').count());
+        assert!(lines[0].starts_with("id,0,1,2,"));
+        assert!(lines[0].ends_with(",58,59"));
+        let row: Vec<u32> = lines[2].split(',').map(|n| n.parse().unwrap()).collect();
+        assert_eq!(99, row[0]);
+        assert_eq!(guards_data[&99].naps(), &row[1..]);
+    }
+
     #[test]
     fn test_most_asleep_guard() {
         let guards_data = read_data(DATA);
@@ -1,35 +1,331 @@
-pub fn remaining_units(units: &str) -> String {
-    let mut units = units.as_bytes().to_owned();
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, Read, Write};
 
-    loop {
-        let units2 = reduce(&units);
-        let reduced = units.len() != units2.len();
-        units = units2;
-        if !reduced {
-            break;
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Reaction<U> {
+    Stable,
+    Destroy,
+    Replace(U),
+}
+
+pub trait ReactionRules {
+    type Unit: Copy + Eq + Hash;
+
+    fn react(&self, a: Self::Unit, b: Self::Unit) -> Reaction<Self::Unit>;
+
+    fn unit_type(&self, unit: Self::Unit) -> Self::Unit {
+        unit
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct AocRules;
+
+impl ReactionRules for AocRules {
+    type Unit = u8;
+
+    fn react(&self, a: u8, b: u8) -> Reaction<u8> {
+        if is_reducable(a, b) {
+            Reaction::Destroy
+        } else {
+            Reaction::Stable
         }
     }
 
-    String::from_utf8(units).unwrap()
+    fn unit_type(&self, unit: u8) -> u8 {
+        unit.to_ascii_lowercase()
+    }
 }
 
-fn reduce(units: &[u8]) -> Vec<u8> {
-    let mut result: Vec<u8> = Vec::with_capacity(units.len());
+/// Characters are of the same type when they have the same simple Unicode
+/// case folding, so `ẞ` and `ß` or `K` (Kelvin sign) and `k` share a type,
+/// while `ß` and `S` do not. Like in the AoC rules two units only react when
+/// their polarity differs too, that is exactly one of them is lowercase.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct CaseFoldRules;
 
-    let mut cursor = units.iter().peekable();
-    while let Some(c) = cursor.next() {
-        if let Some(&next_c) = cursor.peek() {
-            if is_reducable(*c, *next_c) {
-                cursor.next();
-            } else {
-                result.push(*c);
-            }
+fn single_char<I: Iterator<Item = char>>(mut chars: I) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+// Simple case folding is the lowercase form, unified through the uppercase
+// form for the few letters with several lowercase variants (like `ς` and `σ`).
+// Mappings to several characters are not simple ones and are skipped.
+fn case_fold(c: char) -> char {
+    let lower = single_char(c.to_lowercase()).unwrap_or(c);
+    single_char(lower.to_uppercase())
+        .and_then(|upper| single_char(upper.to_lowercase()))
+        .unwrap_or(lower)
+}
+
+impl ReactionRules for CaseFoldRules {
+    type Unit = char;
+
+    fn react(&self, a: char, b: char) -> Reaction<char> {
+        if a.is_lowercase() != b.is_lowercase() && self.unit_type(a) == self.unit_type(b) {
+            Reaction::Destroy
         } else {
-            result.push(*c);
+            Reaction::Stable
+        }
+    }
+
+    fn unit_type(&self, unit: char) -> char {
+        case_fold(unit)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PairRules<U> {
+    reactions: HashMap<(U, U), Reaction<U>>,
+}
+
+impl<U: Eq + Hash + Copy> PairRules<U> {
+    pub fn new() -> PairRules<U> {
+        PairRules {
+            reactions: HashMap::new(),
+        }
+    }
+
+    pub fn add_pair(&mut self, a: U, b: U) -> &mut Self {
+        self.reactions.insert((a, b), Reaction::Destroy);
+        self.reactions.insert((b, a), Reaction::Destroy);
+        self
+    }
+
+    /// Unlike `add_pair` this only applies with `a` followed by `b`.
+    pub fn add_reaction(&mut self, a: U, b: U, product: U) -> &mut Self {
+        self.reactions.insert((a, b), Reaction::Replace(product));
+        self
+    }
+}
+
+impl<U: Eq + Hash + Copy> Default for PairRules<U> {
+    fn default() -> Self {
+        PairRules::new()
+    }
+}
+
+impl<U: Eq + Hash + Copy> ReactionRules for PairRules<U> {
+    type Unit = U;
+
+    fn react(&self, a: U, b: U) -> Reaction<U> {
+        self.reactions
+            .get(&(a, b))
+            .cloned()
+            .unwrap_or(Reaction::Stable)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct ReactionStep<U> {
+    pub position: usize,
+    pub pair: (U, U),
+    pub product: Option<U>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReactionStats<U> {
+    reactions: HashMap<U, u32>,
+    max_depth: usize,
+    trace: Option<Vec<ReactionStep<U>>>,
+}
+
+impl<U: Copy + Eq + Hash> ReactionStats<U> {
+    pub fn new(with_trace: bool) -> ReactionStats<U> {
+        ReactionStats {
+            reactions: HashMap::new(),
+            max_depth: 0,
+            trace: if with_trace { Some(Vec::new()) } else { None },
+        }
+    }
+
+    pub fn reactions(&self) -> &HashMap<U, u32> {
+        &self.reactions
+    }
+
+    pub fn reaction_count(&self) -> u32 {
+        self.reactions.values().sum()
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn trace(&self) -> Option<&[ReactionStep<U>]> {
+        self.trace.as_deref()
+    }
+
+    fn record(&mut self, unit_type: U, step: ReactionStep<U>) {
+        *self.reactions.entry(unit_type).or_insert(0) += 1;
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(step);
+        }
+    }
+}
+
+impl<U: Copy + Into<char>> ReactionStats<U> {
+    pub fn write_trace<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for step in self.trace.iter().flatten() {
+            let (a, b) = step.pair;
+            write!(out, "{}\t{}{}", step.position, a.into(), b.into())?;
+            if let Some(product) = step.product {
+                write!(out, "\t{}", product.into())?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Reactor<'r, R: ReactionRules> {
+    rules: &'r R,
+    stack: Vec<R::Unit>,
+    position: usize,
+    stats: Option<ReactionStats<R::Unit>>,
+}
+
+impl<'r, R: ReactionRules> Reactor<'r, R> {
+    pub fn new(rules: &'r R) -> Reactor<'r, R> {
+        Reactor {
+            rules,
+            stack: Vec::new(),
+            position: 0,
+            stats: None,
+        }
+    }
+
+    pub fn with_stats(rules: &'r R, with_trace: bool) -> Reactor<'r, R> {
+        Reactor {
+            stats: Some(ReactionStats::new(with_trace)),
+            ..Reactor::new(rules)
+        }
+    }
+
+    pub fn push(&mut self, mut unit: R::Unit) {
+        loop {
+            let top = match self.stack.last() {
+                Some(&top) => top,
+                None => {
+                    self.stack.push(unit);
+                    break;
+                }
+            };
+
+            let product = match self.rules.react(top, unit) {
+                Reaction::Stable => {
+                    self.stack.push(unit);
+                    break;
+                }
+                Reaction::Destroy => None,
+                Reaction::Replace(product) => Some(product),
+            };
+
+            self.stack.pop();
+            if let Some(stats) = self.stats.as_mut() {
+                let step = ReactionStep {
+                    position: self.position,
+                    pair: (top, unit),
+                    product,
+                };
+                stats.record(self.rules.unit_type(top), step);
+            }
+
+            match product {
+                Some(product) => unit = product,
+                None => break,
+            }
+        }
+
+        if let Some(stats) = self.stats.as_mut() {
+            stats.max_depth = stats.max_depth.max(self.stack.len());
         }
+        self.position += 1;
+    }
+
+    pub fn units(&self) -> &[R::Unit] {
+        &self.stack
+    }
+
+    pub fn stats(&self) -> Option<&ReactionStats<R::Unit>> {
+        self.stats.as_ref()
+    }
+
+    pub fn into_units(self) -> Vec<R::Unit> {
+        self.stack
+    }
+
+    pub fn into_units_and_stats(self) -> (Vec<R::Unit>, Option<ReactionStats<R::Unit>>) {
+        (self.stack, self.stats)
+    }
+}
+
+impl<'r, R: ReactionRules> Extend<R::Unit> for Reactor<'r, R> {
+    fn extend<I: IntoIterator<Item = R::Unit>>(&mut self, units: I) {
+        let units = units.into_iter();
+        self.stack.reserve(units.size_hint().0);
+        units.for_each(|unit| self.push(unit));
+    }
+}
+
+// ASCII whitespace, like the newline ending an input file, is not part of a polymer.
+fn polymer_units(units: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    units
+        .iter()
+        .filter(|unit| !unit.is_ascii_whitespace())
+        .copied()
+}
+
+pub fn remaining_units(units: &str) -> String {
+    String::from_utf8(react(polymer_units(units.as_bytes()), &AocRules)).unwrap()
+}
+
+pub fn remaining_units_with_stats(units: &str, with_trace: bool) -> (String, ReactionStats<u8>) {
+    let mut reactor = Reactor::with_stats(&AocRules, with_trace);
+    reactor.extend(polymer_units(units.as_bytes()));
+    let (units, stats) = reactor.into_units_and_stats();
+    (String::from_utf8(units).unwrap(), stats.unwrap())
+}
+
+pub fn remaining_units_len<T: Read>(reader: T) -> io::Result<usize> {
+    react_reader(reader, &AocRules).map(|units| units.len())
+}
+
+/// Reduces the polymer read in chunks, skipping ASCII whitespace like
+/// `remaining_units` does.
+pub fn react_reader<T, R>(mut reader: T, rules: &R) -> io::Result<Vec<u8>>
+where
+    T: Read,
+    R: ReactionRules<Unit = u8>,
+{
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    let mut reactor = Reactor::new(rules);
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let len = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        polymer_units(&buffer[..len]).for_each(|unit| reactor.push(unit));
     }
 
-    result
+    Ok(reactor.into_units())
+}
+
+pub fn react<R, I>(units: I, rules: &R) -> Vec<R::Unit>
+where
+    R: ReactionRules,
+    I: IntoIterator<Item = R::Unit>,
+{
+    let mut reactor = Reactor::new(rules);
+    reactor.extend(units);
+    reactor.into_units()
 }
 
 fn is_reducable(a: u8, b: u8) -> bool {
@@ -46,17 +342,57 @@ fn is_reducable(a: u8, b: u8) -> bool {
 fn remove_unit(unit: char, units: &str) -> String {
     let a = unit.to_ascii_lowercase();
     let b = unit.to_ascii_uppercase();
-    units.replace(|c| c == a || c == b, "")
+    units.replace([a, b], "")
+}
+
+/// Measures the polymer without each unit type, in parallel.
+fn removal_search<T, F>(units: &str, measure: F) -> Vec<(char, T)>
+where
+    T: Send,
+    F: Fn(&str) -> T + Sync,
+{
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (b'a'..=b'z')
+            .map(|unit| {
+                let (units, measure) = (&units, &measure);
+                scope.spawn(move || {
+                    let unit = char::from(unit);
+                    (unit, measure(&remove_unit(unit, units)))
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    })
 }
 
 pub fn shortest_polymer_length(units: &str) -> u32 {
-    (0..26)
-        .map(|i| {
-            let c = char::from(65 + i);
-            remaining_units(&remove_unit(c, units)).len()
-        })
-        .min()
-        .unwrap() as u32
+    // removing a unit type never undoes a reaction, so reduce only once
+    removal_search(&remaining_units(units), |units| {
+        remaining_units(units).len()
+    })
+    .into_iter()
+    .map(|(_, len)| len)
+    .min()
+    .unwrap() as u32
+}
+
+/// Remaining length and reaction statistics of the original polymer with each
+/// unit type removed, shortest first.
+pub fn unit_removal_stats(units: &str) -> Vec<(char, usize, ReactionStats<u8>)> {
+    let mut results = removal_search(units, |units| {
+        let (units, stats) = remaining_units_with_stats(units, false);
+        (units.len(), stats)
+    })
+    .into_iter()
+    .map(|(unit, (len, stats))| (unit, len, stats))
+    .collect::<Vec<_>>();
+    results.sort_by_key(|&(unit, len, _)| (len, unit));
+
+    results
 }
 
 #[cfg(test)]
@@ -86,6 +422,142 @@ mod tests {
         assert_eq!("abCBAc", remaining_units(&remove_unit('D', DATA)));
     }
 
+    #[test]
+    fn test_react() {
+        assert_eq!(b"".to_vec(), react(b"aA".iter().cloned(), &AocRules));
+        assert_eq!(b"".to_vec(), react(b"abBA".iter().cloned(), &AocRules));
+        assert_eq!(b"abAB".to_vec(), react(b"abAB".iter().cloned(), &AocRules));
+        assert_eq!(
+            b"aabAAB".to_vec(),
+            react(b"aabAAB".iter().cloned(), &AocRules)
+        );
+    }
+
+    #[test]
+    fn test_case_fold_rules() {
+        let units: String = react("äöÖÄxαΑ".chars(), &CaseFoldRules)
+            .into_iter()
+            .collect();
+        assert_eq!("x", units);
+        assert_eq!(vec!['a', 'a'], react("aa".chars(), &CaseFoldRules));
+        assert_eq!(Vec::<char>::new(), react("ςΣ".chars(), &CaseFoldRules));
+        assert_eq!(vec!['σ', 'ς'], react("σς".chars(), &CaseFoldRules));
+        assert_eq!(vec!['ß', 'S'], react("ßS".chars(), &CaseFoldRules));
+        assert_eq!(Vec::<char>::new(), react("ẞß".chars(), &CaseFoldRules));
+        assert_eq!(
+            Vec::<char>::new(),
+            react("\u{212A}k".chars(), &CaseFoldRules)
+        );
+        assert_eq!(
+            vec!['\u{212A}', 'K'],
+            react("\u{212A}K".chars(), &CaseFoldRules)
+        );
+        assert_eq!('σ', CaseFoldRules.unit_type('ς'));
+        assert_eq!('ß', CaseFoldRules.unit_type('ß'));
+        assert_eq!('ß', CaseFoldRules.unit_type('ẞ'));
+        assert_eq!('k', CaseFoldRules.unit_type('\u{212A}'));
+        assert_eq!('İ', CaseFoldRules.unit_type('İ'));
+    }
+
+    #[test]
+    fn test_pair_rules() {
+        let mut rules = PairRules::new();
+        rules.add_pair(1, 2).add_reaction(3, 4, 1);
+        assert_eq!(Reaction::Destroy, rules.react(2, 1));
+        assert_eq!(Reaction::Replace(1), rules.react(3, 4));
+        assert_eq!(Reaction::Stable, rules.react(4, 3));
+        assert_eq!(vec![0, 5], react(vec![0, 2, 3, 4, 5], &rules));
+        assert_eq!(vec![4, 3], react(vec![4, 3], &rules));
+    }
+
+    #[test]
+    fn test_remaining_units_with_stats() {
+        let (units, stats) = remaining_units_with_stats(DATA, true);
+        assert_eq!("dabCBAcaDA", units);
+        assert_eq!(3, stats.reaction_count());
+        assert_eq!(Some(&1), stats.reactions().get(&b'a'));
+        assert_eq!(Some(&2), stats.reactions().get(&b'c'));
+        assert_eq!(10, stats.max_depth());
+        assert_eq!(
+            Some(
+                &[
+                    ReactionStep {
+                        position: 5,
+                        pair: (b'c', b'C'),
+                        product: None
+                    },
+                    ReactionStep {
+                        position: 6,
+                        pair: (b'A', b'a'),
+                        product: None
+                    },
+                    ReactionStep {
+                        position: 11,
+                        pair: (b'c', b'C'),
+                        product: None
+                    },
+                ][..]
+            ),
+            stats.trace()
+        );
+
+        let mut out = Vec::new();
+        stats.write_trace(&mut out).unwrap();
+        assert_eq!("5\tcC\n6\tAa\n11\tcC\n", String::from_utf8(out).unwrap());
+
+        let (_, stats) = remaining_units_with_stats(DATA, false);
+        assert_eq!(None, stats.trace());
+    }
+
+    #[test]
+    fn test_reactor_replacement_stats() {
+        let mut rules = PairRules::new();
+        rules.add_pair('x', 'y').add_reaction('a', 'b', 'y');
+        let mut reactor = Reactor::with_stats(&rules, true);
+        reactor.extend("xab".chars());
+        assert!(reactor.units().is_empty());
+        let stats = reactor.stats().unwrap();
+        assert_eq!(Some(&1), stats.reactions().get(&'a'));
+        assert_eq!(Some(&1), stats.reactions().get(&'x'));
+        assert_eq!(2, stats.max_depth());
+        assert_eq!(
+            Some(ReactionStep {
+                position: 2,
+                pair: ('x', 'y'),
+                product: None
+            }),
+            stats.trace().unwrap().last().cloned()
+        );
+    }
+
+    #[test]
+    fn test_unit_removal_stats() {
+        let stats = unit_removal_stats(DATA);
+        assert_eq!(26, stats.len());
+        assert_eq!(('c', 4), (stats[0].0, stats[0].1));
+        assert_eq!(3, stats[0].2.reaction_count());
+        // removing an absent unit counts all reactions of the input
+        let (unit, len, stats) = &stats[25];
+        assert_eq!(('z', 10, 3), (*unit, *len, stats.reaction_count()));
+    }
+
+    #[test]
+    fn test_react_reader() {
+        let input = format!("{}\n", DATA);
+        assert_eq!(
+            b"dabCBAcaDA".to_vec(),
+            react_reader(input.as_bytes(), &AocRules).unwrap()
+        );
+        assert_eq!(10, remaining_units_len(input.as_bytes()).unwrap());
+
+        assert_eq!("", remaining_units("aA\n"));
+        assert_eq!(0, remaining_units_len("aA\n".as_bytes()).unwrap());
+        assert_eq!("ab", remaining_units("a\r\nb\n"));
+
+        let large = "aBcD".repeat(50_000) + &"dCbA".repeat(50_000);
+        assert_eq!(0, remaining_units_len(large.as_bytes()).unwrap());
+    }
+
     #[test]
     fn test_shortest_polymer_length() {
         assert_eq!(4, shortest_polymer_length(DATA));
@@ -1,25 +1,35 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::iter::Sum;
 use std::num::ParseIntError;
 use std::str::FromStr;
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-pub struct Pt2 {
-    x: i32,
-    y: i32,
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Point<const N: usize> {
+    coords: [i64; N],
 }
 
-impl Pt2 {
-    pub fn new(x: i32, y: i32) -> Pt2 {
-        Pt2 { x, y }
+pub type Pt2 = Point<2>;
+pub type Pt3 = Point<3>;
+
+impl<const N: usize> Point<N> {
+    pub fn new(coords: [i64; N]) -> Point<N> {
+        Point { coords }
     }
 
-    pub fn manhatten_distance(&self, pt: &Pt2) -> u32 {
-        let dx = self.x - pt.x;
-        let dy = self.y - pt.y;
-        (dx.abs() + dy.abs()) as u32
+    pub fn coords(&self) -> &[i64; N] {
+        &self.coords
     }
 
-    pub fn min_distance(&self, points: &[Pt2]) -> u32 {
+    pub fn manhatten_distance(&self, pt: &Point<N>) -> u64 {
+        self.coords
+            .iter()
+            .zip(pt.coords.iter())
+            .map(|(a, b)| (a - b).unsigned_abs())
+            .sum()
+    }
+
+    pub fn min_distance(&self, points: &[Point<N>]) -> u64 {
         points
             .iter()
             .map(|p| self.manhatten_distance(p))
@@ -27,7 +37,7 @@ impl Pt2 {
             .unwrap()
     }
 
-    pub fn max_distance(&self, points: &[Pt2]) -> u32 {
+    pub fn max_distance(&self, points: &[Point<N>]) -> u64 {
         points
             .iter()
             .map(|p| self.manhatten_distance(p))
@@ -36,113 +46,846 @@ impl Pt2 {
     }
 }
 
-impl FromStr for Pt2 {
-    type Err = ParseIntError;
+#[derive(PartialEq, Debug)]
+pub struct ParsePointError();
+
+impl From<ParseIntError> for ParsePointError {
+    fn from(_error: ParseIntError) -> Self {
+        ParsePointError()
+    }
+}
+
+impl<const N: usize> FromStr for Point<N> {
+    type Err = ParsePointError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // 1, 3
-        let coords: Vec<&str> = s.split(',').map(|coord| coord.trim()).collect();
-        Ok(Pt2::new(coords[0].parse()?, coords[1].parse()?))
+        // 1, 3, -2
+        let coords = s
+            .split(',')
+            .map(|coord| coord.trim().parse())
+            .collect::<Result<Vec<i64>, _>>()?;
+        let coords = coords.try_into().map_err(|_| ParsePointError())?;
+        Ok(Point::new(coords))
     }
 }
 
-fn max_distance(points: &[Pt2]) -> u32 {
-    points[..points.len() - 1]
-        .iter()
-        .enumerate()
-        .map(|(i, p)| p.max_distance(&points[i + 1..]))
-        .max()
-        .unwrap()
+pub trait Metric {
+    type Distance: PartialOrd + Copy + Sum;
+
+    fn distance<const N: usize>(&self, a: &Point<N>, b: &Point<N>) -> Self::Distance;
+
+    /// How far along one axis a point may lie outside the bounding box of
+    /// `point_count` points while its distance sum to them can still stay
+    /// below `threshold`. Points that far out are at least that far from
+    /// every point, so this only has to bound a single distance.
+    fn reach(&self, threshold: Self::Distance, point_count: usize) -> u64;
+
+    /// Area of the region nearest to each point, `None` for infinite ones.
+    ///
+    /// The default labels every cell of a grid reaching past the bounding
+    /// box by its extent along each axis, and takes regions touching the
+    /// grid's edge as infinite. This is exact as long as no finite region
+    /// reaches that far out, so metrics that cannot promise that, or that
+    /// know a faster exact algorithm, override it.
+    fn areas(&self, points: &[Pt2]) -> Vec<Option<u64>> {
+        let (mut min, mut max) = bounding_box(points);
+        for k in 0..2 {
+            let padding = max.coords[k] - min.coords[k] + 1;
+            min.coords[k] -= padding;
+            max.coords[k] += padding;
+        }
+        AreaMap::with_metric(points, self, &min, &max).areas(points.len())
+    }
 }
 
-fn bounding_box(points: &[Pt2]) -> (Pt2, Pt2) {
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Manhattan;
+
+impl Metric for Manhattan {
+    type Distance = u64;
+
+    fn distance<const N: usize>(&self, a: &Point<N>, b: &Point<N>) -> u64 {
+        a.manhatten_distance(b)
+    }
+
+    fn reach(&self, threshold: u64, point_count: usize) -> u64 {
+        threshold.saturating_sub(1) / point_count as u64
+    }
+
+    fn areas(&self, points: &[Pt2]) -> Vec<Option<u64>> {
+        AreaMap::new(points).areas(points.len())
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Chebyshev;
+
+impl Metric for Chebyshev {
+    type Distance = u64;
+
+    fn distance<const N: usize>(&self, a: &Point<N>, b: &Point<N>) -> u64 {
+        a.coords
+            .iter()
+            .zip(b.coords.iter())
+            .map(|(a, b)| (a - b).unsigned_abs())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn reach(&self, threshold: u64, point_count: usize) -> u64 {
+        threshold.saturating_sub(1) / point_count as u64
+    }
+
+    fn areas(&self, points: &[Pt2]) -> Vec<Option<u64>> {
+        chebyshev_areas(points)
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SquaredEuclidean;
+
+impl Metric for SquaredEuclidean {
+    type Distance = u64;
+
+    fn distance<const N: usize>(&self, a: &Point<N>, b: &Point<N>) -> u64 {
+        a.coords
+            .iter()
+            .zip(b.coords.iter())
+            .map(|(a, b)| (a - b).unsigned_abs().pow(2))
+            .sum()
+    }
+
+    fn reach(&self, threshold: u64, point_count: usize) -> u64 {
+        let limit = threshold.saturating_sub(1) / point_count as u64;
+        let mut reach = (limit as f64).sqrt() as u64;
+        while reach * reach > limit {
+            reach -= 1;
+        }
+        while (reach + 1) * (reach + 1) <= limit {
+            reach += 1;
+        }
+        reach
+    }
+
+    fn areas(&self, points: &[Pt2]) -> Vec<Option<u64>> {
+        euclidean_areas(points)
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Euclidean;
+
+impl Metric for Euclidean {
+    type Distance = f64;
+
+    fn distance<const N: usize>(&self, a: &Point<N>, b: &Point<N>) -> f64 {
+        (SquaredEuclidean.distance(a, b) as f64).sqrt()
+    }
+
+    fn reach(&self, threshold: f64, point_count: usize) -> u64 {
+        (threshold / point_count as f64).ceil().max(1.0) as u64 - 1
+    }
+
+    fn areas(&self, points: &[Pt2]) -> Vec<Option<u64>> {
+        euclidean_areas(points)
+    }
+}
+
+fn bounding_box<const N: usize>(points: &[Point<N>]) -> (Point<N>, Point<N>) {
     points.iter().fold(
-        (
-            Pt2::new(std::i32::MAX, std::i32::MAX),
-            Pt2::new(std::i32::MIN, std::i32::MIN),
-        ),
-        |bbox, p| {
-            (
-                Pt2::new(p.x.min(bbox.0.x), p.y.min(bbox.0.y)),
-                Pt2::new(p.x.max(bbox.1.x), p.y.max(bbox.1.y)),
-            )
+        (Point::new([i64::MAX; N]), Point::new([i64::MIN; N])),
+        |(mut min, mut max), p| {
+            for k in 0..N {
+                min.coords[k] = min.coords[k].min(p.coords[k]);
+                max.coords[k] = max.coords[k].max(p.coords[k]);
+            }
+            (min, max)
         },
     )
 }
 
-pub fn largest_area(points: &[Pt2]) -> u32 {
-    let dist = max_distance(points) as i32;
-    let bbox = bounding_box(points);
-    let bbox = (
-        Pt2::new(bbox.0.x - dist, bbox.0.y - dist),
-        Pt2::new(bbox.1.x + dist, bbox.1.y + dist),
-    );
+// All integer points of a box, stored row-major with the first axis varying fastest.
+struct Grid<const N: usize> {
+    origin: Point<N>,
+    dims: [usize; N],
+    strides: [usize; N],
+    len: usize,
+}
 
-    let is_on_edge =
-        |p: &Pt2| p.x == bbox.0.x || p.x == bbox.1.x || p.y == bbox.0.y || p.y == bbox.1.y;
+impl<const N: usize> Grid<N> {
+    fn new(min: &Point<N>, max: &Point<N>) -> Grid<N> {
+        let mut dims = [0; N];
+        let mut strides = [0; N];
+        let mut len = 1;
+        for k in 0..N {
+            dims[k] = (max.coords[k] - min.coords[k] + 1) as usize;
+            strides[k] = len;
+            len *= dims[k];
+        }
 
-    let mut excludes: HashSet<Pt2> = HashSet::new();
-    let area_map = (bbox.0.y..=bbox.1.y).fold(HashMap::new(), |m: HashMap<Pt2, u32>, y| {
-        (bbox.0.x..=bbox.1.x).fold(m, |m, x| {
-            let mut m = m;
-            let p = Pt2::new(x, y);
-            let distances = points
-                .iter()
-                .map(|p2| (p2, p.manhatten_distance(p2)))
-                .collect::<Vec<_>>();
-            let min_distance = distances.iter().map(|(_, d)| d).min().unwrap();
-            let min_points = distances
-                .iter()
-                .filter(|(_, d)| d == min_distance)
-                .map(|&(p, _)| p)
-                .collect::<Vec<_>>();
-
-            if min_points.len() == 1 {
-                let k = min_points[0];
-                match m.get_mut(k) {
-                    Some(counter) => *counter += 1,
-                    None => {
-                        m.insert(k.clone(), 1);
+        Grid {
+            origin: *min,
+            dims,
+            strides,
+            len,
+        }
+    }
+
+    fn index_of(&self, p: &Point<N>) -> usize {
+        (0..N)
+            .map(|k| (p.coords[k] - self.origin.coords[k]) as usize * self.strides[k])
+            .sum()
+    }
+
+    fn coord(&self, index: usize, k: usize) -> usize {
+        index / self.strides[k] % self.dims[k]
+    }
+
+    fn point_at(&self, index: usize) -> Point<N> {
+        let mut p = self.origin;
+        for k in 0..N {
+            p.coords[k] += self.coord(index, k) as i64;
+        }
+        p
+    }
+
+    fn points(&self) -> impl Iterator<Item = Point<N>> + '_ {
+        (0..self.len).map(move |index| self.point_at(index))
+    }
+
+    fn is_on_edge(&self, index: usize) -> bool {
+        (0..N).any(|k| {
+            let coord = self.coord(index, k);
+            coord == 0 || coord + 1 == self.dims[k]
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Owner {
+    Nearest(usize),
+    Tie,
+}
+
+impl Owner {
+    fn merge(self, other: Owner) -> Owner {
+        if self == other {
+            self
+        } else {
+            Owner::Tie
+        }
+    }
+}
+
+struct AreaMap<const N: usize> {
+    grid: Grid<N>,
+    owners: Vec<Owner>,
+}
+
+impl<const N: usize> AreaMap<N> {
+    fn new(points: &[Point<N>]) -> AreaMap<N> {
+        AreaMap::with_margin(points, 0)
+    }
+
+    // Areas stay the same with any margin: outside the bounding box every
+    // cell belongs to the region of the nearest cell on its edge.
+    fn with_margin(points: &[Point<N>], margin: i64) -> AreaMap<N> {
+        let (mut min, mut max) = bounding_box(points);
+        for k in 0..N {
+            min.coords[k] -= margin;
+            max.coords[k] += margin;
+        }
+        let grid = Grid::new(&min, &max);
+
+        let mut owners = vec![Owner::Tie; grid.len];
+        let mut distances = vec![u32::MAX; grid.len];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+
+        for (i, p) in points.iter().enumerate() {
+            let index = grid.index_of(p);
+            if distances[index] == 0 {
+                owners[index] = Owner::Tie;
+            } else {
+                distances[index] = 0;
+                owners[index] = Owner::Nearest(i);
+                queue.push_back(index);
+            }
+        }
+
+        while let Some(index) = queue.pop_front() {
+            let distance = distances[index] + 1;
+            let owner = owners[index];
+
+            for k in 0..N {
+                let coord = grid.coord(index, k);
+                let neighbours = [
+                    (coord > 0, index.wrapping_sub(grid.strides[k])),
+                    (coord + 1 < grid.dims[k], index + grid.strides[k]),
+                ];
+                for &(_, neighbour) in neighbours.iter().filter(|(valid, _)| *valid) {
+                    if distances[neighbour] == u32::MAX {
+                        distances[neighbour] = distance;
+                        owners[neighbour] = owner;
+                        queue.push_back(neighbour);
+                    } else if distances[neighbour] == distance {
+                        owners[neighbour] = owners[neighbour].merge(owner);
                     }
                 }
+            }
+        }
+
+        AreaMap { grid, owners }
+    }
+
+    fn with_metric<M: Metric + ?Sized>(
+        points: &[Point<N>],
+        metric: &M,
+        min: &Point<N>,
+        max: &Point<N>,
+    ) -> AreaMap<N> {
+        let grid = Grid::new(min, max);
+
+        let owners = grid
+            .points()
+            .map(|p| {
+                let mut nearest: Option<(M::Distance, Owner)> = None;
+                for (i, p2) in points.iter().enumerate() {
+                    let distance = metric.distance(&p, p2);
+                    nearest = match nearest {
+                        Some((d, _)) if distance < d => Some((distance, Owner::Nearest(i))),
+                        Some((d, _)) if distance == d => Some((d, Owner::Tie)),
+                        None => Some((distance, Owner::Nearest(i))),
+                        nearest => nearest,
+                    };
+                }
+                nearest.unwrap().1
+            })
+            .collect();
+
+        AreaMap { grid, owners }
+    }
 
-                if is_on_edge(&p) {
-                    excludes.insert(k.clone());
+    fn areas(&self, point_count: usize) -> Vec<Option<u64>> {
+        let mut areas = vec![Some(0); point_count];
+        for (index, owner) in self.owners.iter().enumerate() {
+            if let Owner::Nearest(i) = *owner {
+                if self.grid.is_on_edge(index) {
+                    areas[i] = None;
+                } else if let Some(area) = areas[i].as_mut() {
+                    *area += 1;
                 }
             }
+        }
+        areas
+    }
+}
+
+/// Largest finite area, `None` if every region is infinite.
+pub fn largest_area<const N: usize>(points: &[Point<N>]) -> Option<u64> {
+    AreaMap::new(points)
+        .areas(points.len())
+        .into_iter()
+        .flatten()
+        .max()
+}
+
+/// Largest finite area, `None` if every region is infinite.
+pub fn largest_area_with<M: Metric>(points: &[Pt2], metric: &M) -> Option<u64> {
+    metric.areas(points).into_iter().flatten().max()
+}
+
+// With u = x + y and v = x - y the Chebyshev distance is half the Manhattan
+// distance in (u, v), and a diagonal step away from the (u, v) box of all
+// points adds one to every distance. Such a step moves u or v by two, so every
+// cell has the owner of a cell at most two outside that box, and regions
+// owning such a cell are infinite.
+fn chebyshev_areas(points: &[Pt2]) -> Vec<Option<u64>> {
+    let rotate = |p: &Pt2| (p.coords[0] + p.coords[1], p.coords[0] - p.coords[1]);
+    let (u_min, u_max, v_min, v_max) = points.iter().map(rotate).fold(
+        (i64::MAX, i64::MIN, i64::MAX, i64::MIN),
+        |(u_min, u_max, v_min, v_max), (u, v)| {
+            (u_min.min(u), u_max.max(u), v_min.min(v), v_max.max(v))
+        },
+    );
+    let min = Pt2::new([
+        (u_min + v_min - 4).div_euclid(2),
+        (u_min - v_max - 4).div_euclid(2),
+    ]);
+    let max = Pt2::new([
+        (u_max + v_max + 5).div_euclid(2),
+        (u_max - v_min + 5).div_euclid(2),
+    ]);
+    let map = AreaMap::with_metric(points, &Chebyshev, &min, &max);
+
+    let mut areas = vec![Some(0); points.len()];
+    for (owner, p) in map.owners.iter().zip(map.grid.points()) {
+        let (u, v) = rotate(&p);
+        if u < u_min - 2 || u > u_max + 2 || v < v_min - 2 || v > v_max + 2 {
+            continue;
+        }
+        if let Owner::Nearest(i) = *owner {
+            if u < u_min || u > u_max || v < v_min || v > v_max {
+                areas[i] = None;
+            } else if let Some(area) = areas[i].as_mut() {
+                *area += 1;
+            }
+        }
+    }
+    areas
+}
+
+// A Euclidean region is the set of cells on the near side of the bisectors to
+// all other points, which is unbounded exactly when its point lies on the
+// convex hull. Bounded regions are counted row by row.
+fn euclidean_areas(points: &[Pt2]) -> Vec<Option<u64>> {
+    points
+        .iter()
+        .map(|point| {
+            let offsets: Vec<(i64, i64)> = points
+                .iter()
+                .map(|p| (p.coords[0] - point.coords[0], p.coords[1] - point.coords[1]))
+                .filter(|&offset| offset != (0, 0))
+                .collect();
+            if offsets.len() + 1 < points.len() {
+                Some(0)
+            } else if is_on_hull(&offsets) {
+                None
+            } else {
+                Some(bounded_euclidean_area(point, &offsets))
+            }
+        })
+        .collect()
+}
 
-            m
+// Whether all offsets fit in a closed half-plane, whose edge can be turned
+// until it runs along one of them.
+fn is_on_hull(offsets: &[(i64, i64)]) -> bool {
+    let cross = |(x1, y1): (i64, i64), (x2, y2): (i64, i64)| {
+        (x1 as i128 * y2 as i128 - y1 as i128 * x2 as i128).signum()
+    };
+    offsets.is_empty()
+        || offsets.iter().any(|&edge| {
+            let sides: Vec<i128> = offsets.iter().map(|&offset| cross(edge, offset)).collect();
+            sides.iter().all(|&side| side >= 0) || sides.iter().all(|&side| side <= 0)
         })
-    });
+}
 
-    let (_pt, area) = area_map
+fn bounded_euclidean_area(point: &Pt2, offsets: &[(i64, i64)]) -> u64 {
+    let [px, py] = point.coords;
+    // A cell (x, y) is nearer to `point` than to `point + (a, b)` exactly when
+    // 2ax + 2by < |point + (a, b)|² - |point|².
+    let bisectors: Vec<(i128, i128, i128)> = offsets
         .iter()
-        .filter(|&(p, _)| !excludes.contains(p))
-        .max_by(|&(_, a1), &(_, a2)| a1.cmp(a2))
-        .unwrap();
+        .map(|&(a, b)| {
+            let (a, b) = (a as i128, b as i128);
+            let (px, py) = (px as i128, py as i128);
+            (a, b, a * a + b * b + 2 * (a * px + b * py))
+        })
+        .collect();
+
+    // Cells in row y, or None once the row misses the region entirely.
+    let row = |y: i64| {
+        // Open bounds on x as fractions with positive denominators.
+        let mut lower: Option<(i128, i128)> = None;
+        let mut upper: Option<(i128, i128)> = None;
+        for &(a, b, c) in &bisectors {
+            let rest = c - 2 * b * y as i128;
+            match a.signum() {
+                0 if rest <= 0 => return None,
+                0 => {}
+                1 => {
+                    if upper.is_none_or(|(n, d)| rest * d < n * 2 * a) {
+                        upper = Some((rest, 2 * a));
+                    }
+                }
+                _ => {
+                    if lower.is_none_or(|(n, d)| -rest * d > n * -2 * a) {
+                        lower = Some((-rest, -2 * a));
+                    }
+                }
+            }
+        }
+        let ((ln, ld), (un, ud)) = (lower?, upper?);
+        if ln * ud >= un * ld {
+            return None;
+        }
+        let first = ln.div_euclid(ld) + 1;
+        let last = -(-un).div_euclid(ud) - 1;
+        Some((last - first + 1).max(0) as u64)
+    };
 
-    *area
+    let up = (py..).map_while(row).sum::<u64>();
+    let down = (i64::MIN..py).rev().map_while(row).sum::<u64>();
+    up + down
 }
 
-pub fn region_size(points: &[Pt2], threshold: u32) -> u32 {
-    let bbox = bounding_box(points);
-    let dist = max_distance(points) as i32; // TODO: use better value
-    let bbox = (
-        Pt2::new(bbox.0.x - dist, bbox.0.y - dist),
-        Pt2::new(bbox.1.x + dist, bbox.1.y + dist),
-    );
+/// Number of points whose distance sum stays below threshold, `None` if that
+/// count does not fit in a `u128`.
+pub fn region_size<const N: usize>(points: &[Point<N>], threshold: u64) -> Option<u128> {
+    let sums: Vec<AxisSums> = (0..N)
+        .map(|k| axis_distance_sums(points.iter().map(|p| p.coords[k]), threshold))
+        .collect();
+    count_below(&sums, threshold, &[])
+}
 
-    (bbox.0.y..=bbox.1.y)
-        .map(|y| (bbox.0.x..=bbox.1.x).map(move |x| Pt2::new(x, y)))
-        .flatten()
+fn checked_sum<I: Iterator<Item = Option<u128>>>(mut counts: I) -> Option<u128> {
+    counts.try_fold(0u128, |total, count| total.checked_add(count?))
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+struct Progression {
+    start: u64,
+    step: u64,
+    len: u64,
+}
+
+impl Progression {
+    fn count_below(&self, threshold: u64) -> u64 {
+        if self.start >= threshold {
+            0
+        } else {
+            self.len.min((threshold - self.start - 1) / self.step + 1)
+        }
+    }
+}
+
+// Number of pairs from two progressions with the same step whose sum stays below threshold.
+fn count_pairs_below(a: &Progression, b: &Progression, threshold: u64) -> u128 {
+    if a.start.saturating_add(b.start) >= threshold {
+        return 0;
+    }
+    // pairs of indices (i, j) with i + j < steps, minus those past the end of either;
+    // steps fits in 65 bits, so each triangle fits in a u128 when halving first
+    let steps = u128::from((threshold - a.start - b.start - 1) / a.step) + 1;
+    let triangle = |k: u128| {
+        if k.is_multiple_of(2) {
+            k / 2 * (k + 1)
+        } else {
+            k * k.div_ceil(2)
+        }
+    };
+    let (a_len, b_len) = (u128::from(a.len), u128::from(b.len));
+    (triangle(steps) - triangle(steps.saturating_sub(a_len)))
+        - (triangle(steps.saturating_sub(b_len)) - triangle(steps.saturating_sub(a_len + b_len)))
+}
+
+// Distance sums along one axis: sorted ones inside the bounding box, and the two
+// tails outside of it, which grow by the number of coordinates with every step.
+#[derive(PartialEq, Debug)]
+struct AxisSums {
+    inside: Vec<u64>,
+    tails: [Progression; 2],
+}
+
+impl AxisSums {
+    fn count_below(&self, threshold: u64) -> u128 {
+        self.inside.partition_point(|&sum| sum < threshold) as u128
+            + self
+                .tails
+                .iter()
+                .map(|tail| u128::from(tail.count_below(threshold)))
+                .sum::<u128>()
+    }
+}
+
+// C(n, k), computed so every intermediate value is a binomial coefficient too.
+fn binomial(n: u128, k: u128) -> Option<u128> {
+    (1..=k).try_fold(1u128, |c, i| Some(c.checked_mul(n - k + i)? / i))
+}
+
+// Number of ways to pick one term from each progression, all with the same step,
+// whose sum stays below threshold. These are the index tuples with a sum of at
+// most `steps`, counted by inclusion-exclusion over the indices past their end.
+fn count_terms_below(tails: &[Progression], threshold: u64) -> Option<u128> {
+    let start = match tails
+        .iter()
+        .try_fold(0u64, |sum, tail| sum.checked_add(tail.start))
+    {
+        Some(start) if start < threshold => start,
+        _ => return Some(0),
+    };
+    let steps = match tails.first() {
+        Some(tail) => u128::from((threshold - start - 1) / tail.step),
+        None => return Some(1),
+    };
+
+    let lens: Vec<u128> = tails.iter().map(|tail| u128::from(tail.len)).collect();
+    if lens.contains(&0) {
+        return Some(0);
+    }
+    if lens.iter().map(|len| len - 1).sum::<u128>() <= steps {
+        return lens
+            .iter()
+            .try_fold(1u128, |count, &len| count.checked_mul(len));
+    }
+
+    let k = lens.len() as u128;
+    let (mut added, mut removed) = (0u128, 0u128);
+    for subset in 0..1usize << lens.len() {
+        let excess: u128 = (0..lens.len())
+            .filter(|i| subset & (1 << i) != 0)
+            .map(|i| lens[i])
+            .sum();
+        if excess > steps {
+            continue;
+        }
+        let count = binomial(steps - excess + k, k)?;
+        if subset.count_ones() % 2 == 0 {
+            added = added.checked_add(count)?;
+        } else {
+            removed = removed.checked_add(count)?;
+        }
+    }
+    Some(added - removed)
+}
+
+// Number of combinations picking one sum per axis, plus one term of each of the
+// given tails, whose total stays below threshold. Without such tails the last two
+// axes are counted in closed form. Otherwise sums inside the bounding box are taken
+// one by one, and the tails of every axis are only collected, as their terms are
+// counted together in closed form once each axis is decided.
+fn count_below(sums: &[AxisSums], threshold: u64, tails: &[Progression]) -> Option<u128> {
+    match (sums, tails) {
+        ([], _) => count_terms_below(tails, threshold),
+        ([last], []) => Some(last.count_below(threshold)),
+        ([first, last], []) => {
+            let inside = checked_sum(
+                first
+                    .inside
+                    .iter()
+                    .take_while(|&&sum| sum < threshold)
+                    .map(|&sum| Some(last.count_below(threshold - sum))),
+            );
+            let tails = checked_sum(first.tails.iter().map(|tail| {
+                let by_inside = checked_sum(
+                    last.inside
+                        .iter()
+                        .take_while(|&&sum| sum < threshold)
+                        .map(|&sum| Some(u128::from(tail.count_below(threshold - sum)))),
+                );
+                let by_tails = checked_sum(
+                    last.tails
+                        .iter()
+                        .map(|other| Some(count_pairs_below(tail, other, threshold))),
+                );
+                by_inside?.checked_add(by_tails?)
+            }));
+            inside?.checked_add(tails?)
+        }
+        ([first, rest @ ..], _) => {
+            let inside = checked_sum(
+                first
+                    .inside
+                    .iter()
+                    .take_while(|&&sum| sum < threshold)
+                    .map(|&sum| count_below(rest, threshold - sum, tails)),
+            );
+            let by_tails = checked_sum(first.tails.iter().map(|tail| {
+                let mut tails = tails.to_vec();
+                tails.push(*tail);
+                count_below(rest, threshold, &tails)
+            }));
+            inside?.checked_add(by_tails?)
+        }
+    }
+}
+
+// Sums of distances to all coordinates along one axis, for every position where
+// this sum alone stays below threshold (the region may reach beyond the points).
+fn axis_distance_sums<I: Iterator<Item = i64>>(coords: I, threshold: u64) -> AxisSums {
+    let mut coords: Vec<i64> = coords.collect();
+    coords.sort_unstable();
+    let n = coords.len() as u64;
+    let (min, max) = (coords[0], coords[coords.len() - 1]);
+    let tail = |sum_at_edge: u64| Progression {
+        start: sum_at_edge.saturating_add(n),
+        step: n,
+        len: threshold.saturating_sub(sum_at_edge.saturating_add(1)) / n,
+    };
+
+    let mut inside = Vec::new();
+    let sum_at_min: u64 = coords.iter().map(|&c| (c - min) as u64).sum();
+    let mut sum = sum_at_min;
+    let mut index = 0;
+    for x in min..=max {
+        if sum < threshold {
+            inside.push(sum);
+        }
+        while index < coords.len() && coords[index] <= x {
+            index += 1;
+        }
+        let (before, after) = (index as u64, n - index as u64);
+        sum = sum + before - after;
+    }
+    inside.sort_unstable();
+
+    let sum_at_max = sum - n;
+    AxisSums {
+        inside,
+        tails: [tail(sum_at_min), tail(sum_at_max)],
+    }
+}
+
+pub fn region_size_with<M: Metric, const N: usize>(
+    points: &[Point<N>],
+    threshold: M::Distance,
+    metric: &M,
+) -> u64 {
+    let (mut min, mut max) = bounding_box(points);
+    let dist = metric.reach(threshold, points.len()) as i64;
+    for k in 0..N {
+        min.coords[k] -= dist;
+        max.coords[k] += dist;
+    }
+
+    Grid::new(&min, &max)
+        .points()
         .map(|p| {
             points
                 .iter()
-                .map(|p2| p.manhatten_distance(p2))
-                .sum::<u32>()
+                .map(|p2| metric.distance(&p, p2))
+                .sum::<M::Distance>()
         })
         .filter(|&d| d < threshold)
-        .count() as u32
+        .count() as u64
+}
+
+type Rgb = (u8, u8, u8);
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Cell {
+    Site(Rgb),
+    Finite(Rgb),
+    Infinite(Rgb),
+    Tie,
+    Outline(char),
+}
+
+fn region_color(index: usize) -> Rgb {
+    // spread hues by the golden angle, full saturation and value
+    let hue = (index as f64 * 137.508) % 360.0;
+    let x = 1.0 - ((hue / 60.0) % 2.0 - 1.0).abs();
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let channel = |c: f64| (c * 255.0).round() as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+// Covers the safe region, and at least one cell around the bounding box
+// to outline it.
+fn render_cells(points: &[Pt2], threshold: u64) -> (usize, Vec<(Cell, bool)>) {
+    let margin = Manhattan.reach(threshold, points.len()).max(1) as i64;
+    let map = AreaMap::with_margin(points, margin);
+    let areas = map.areas(points.len());
+    let (min, max) = bounding_box(points);
+
+    let mut cells: Vec<(Cell, bool)> = map
+        .owners
+        .iter()
+        .zip(map.grid.points())
+        .map(|(owner, p)| {
+            let is_safe = points
+                .iter()
+                .map(|p2| p.manhatten_distance(p2))
+                .sum::<u64>()
+                < threshold;
+            let cell = match *owner {
+                Owner::Nearest(i) if areas[i].is_some() => Cell::Finite(region_color(i)),
+                Owner::Nearest(i) => Cell::Infinite(region_color(i)),
+                Owner::Tie => Cell::Tie,
+            };
+            (cell, is_safe)
+        })
+        .collect();
+
+    for (i, p) in points.iter().enumerate() {
+        cells[map.grid.index_of(p)].0 = Cell::Site(region_color(i));
+    }
+
+    let [left, top] = min.coords;
+    let [right, bottom] = max.coords;
+    let (left, top, right, bottom) = (left - 1, top - 1, right + 1, bottom + 1);
+    for x in left..=right {
+        for &y in [top, bottom].iter() {
+            let glyph = match x {
+                x if x == left && y == top => '┌',
+                x if x == right && y == top => '┐',
+                x if x == left => '└',
+                x if x == right => '┘',
+                _ => '─',
+            };
+            cells[map.grid.index_of(&Pt2::new([x, y]))].0 = Cell::Outline(glyph);
+        }
+    }
+    for y in top + 1..bottom {
+        for &x in [left, right].iter() {
+            cells[map.grid.index_of(&Pt2::new([x, y]))].0 = Cell::Outline('│');
+        }
+    }
+
+    (map.grid.dims[0], cells)
+}
+
+pub fn render_ppm(points: &[Pt2], threshold: u64) -> Vec<u8> {
+    let (width, cells) = render_cells(points, threshold);
+
+    let mut image = format!("P6\n{} {}\n255\n", width, cells.len() / width).into_bytes();
+    for (cell, is_safe) in cells {
+        let (r, g, b) = match cell {
+            Cell::Site(_) => (255, 255, 255),
+            Cell::Finite(color) => color,
+            Cell::Infinite((r, g, b)) => (r / 3, g / 3, b / 3),
+            Cell::Tie => (0, 0, 0),
+            Cell::Outline(_) => (128, 128, 128),
+        };
+        let (r, g, b) = if is_safe {
+            (r / 2 + 128, g / 2 + 128, b / 2 + 128)
+        } else {
+            (r, g, b)
+        };
+        image.extend_from_slice(&[r, g, b]);
+    }
+
+    image
+}
+
+pub fn render_ansi(points: &[Pt2], threshold: u64) -> String {
+    let (width, cells) = render_cells(points, threshold);
+    let ansi_color = |(r, g, b): Rgb| {
+        16 + 36 * (u32::from(r) * 5 / 255) + 6 * (u32::from(g) * 5 / 255) + u32::from(b) * 5 / 255
+    };
+
+    cells
+        .chunks(width)
+        .map(|row| {
+            let mut line: String = row
+                .iter()
+                .map(|&(cell, is_safe)| {
+                    let glyph = if is_safe { '#' } else { '+' };
+                    match cell {
+                        Cell::Site(color) => format!("\x1b[0;1;38;5;{}m@", ansi_color(color)),
+                        Cell::Finite(color) => {
+                            format!("\x1b[0;38;5;{}m{}", ansi_color(color), glyph)
+                        }
+                        Cell::Infinite(color) => {
+                            format!("\x1b[0;2;38;5;{}m{}", ansi_color(color), glyph)
+                        }
+                        Cell::Tie => format!("\x1b[0m{}", if is_safe { '#' } else { '.' }),
+                        Cell::Outline(glyph) => format!("\x1b[0m{}", glyph),
+                    }
+                })
+                .collect();
+            line += "\x1b[0m";
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[cfg(test)]
@@ -160,33 +903,335 @@ mod tests {
         assert_eq!(
             data(),
             vec![
-                Pt2::new(1, 1),
-                Pt2::new(1, 6),
-                Pt2::new(8, 3),
-                Pt2::new(3, 4),
-                Pt2::new(5, 5),
-                Pt2::new(8, 9),
+                Pt2::new([1, 1]),
+                Pt2::new([1, 6]),
+                Pt2::new([8, 3]),
+                Pt2::new([3, 4]),
+                Pt2::new([5, 5]),
+                Pt2::new([8, 9]),
             ]
         );
     }
 
     #[test]
-    fn test_max_distance() {
-        assert_eq!(15, max_distance(&data()))
+    fn test_parse_point() {
+        assert_eq!(Ok(Point::new([1, 3])), "1, 3".parse::<Point<2>>());
+        assert_eq!(Ok(Pt3::new([1, -3, 7])), "1,-3, 7".parse::<Pt3>());
+        assert_eq!(
+            Ok(Point::new([1, 2, 3, 4])),
+            "1, 2, 3, 4".parse::<Point<4>>()
+        );
+        assert_eq!(Err(ParsePointError()), "1, 2".parse::<Pt3>());
+        assert_eq!(Err(ParsePointError()), "1, x, 2".parse::<Pt3>());
+    }
+
+    #[test]
+    fn test_point_manhatten_distance() {
+        let a = Pt3::new([1, 2, 3]);
+        let b = Pt3::new([-1, 2, 7]);
+        assert_eq!(6, a.manhatten_distance(&b));
+    }
+
+    #[test]
+    fn test_largest_area_3d() {
+        let mut points: Vec<Pt3> = DATA
+            .iter()
+            .map(|d| format!("{}, 0", d).parse().unwrap())
+            .collect();
+        points.push(Pt3::new([5, 5, -4]));
+        points.push(Pt3::new([5, 5, 4]));
+        assert_eq!(Some(55), largest_area(&points));
+    }
+
+    #[test]
+    fn test_region_size_3d() {
+        let points = [Pt3::new([0, 0, 0])];
+        assert_eq!(Some(1), region_size(&points, 1));
+        assert_eq!(Some(7), region_size(&points, 2));
+        assert_eq!(Some(25), region_size(&points, 3));
+        // octahedral numbers (2r + 1)(2r² + 2r + 3) / 3 with r = threshold - 1
+        assert_eq!(
+            Some(1_333_333_333_331_333_333_333_335_999_999_999_999),
+            region_size(&points, 1_000_000_000_000)
+        );
+
+        let mut points: Vec<Pt3> = DATA
+            .iter()
+            .map(|d| format!("{}, 0", d).parse().unwrap())
+            .collect();
+        points.push(Pt3::new([5, 5, -4]));
+        assert_eq!(
+            Some(u128::from(region_size_with(&points, 120, &Manhattan))),
+            region_size(&points, 120)
+        );
+
+        let points = [Point::new([0, 1, 2, 3]), Point::new([3, -1, 0, 2])];
+        assert_eq!(
+            Some(u128::from(region_size_with(&points, 20, &Manhattan))),
+            region_size(&points, 20)
+        );
     }
 
     #[test]
     fn test_bounding_box() {
-        assert_eq!((Pt2::new(1, 1), Pt2::new(8, 9)), bounding_box(&data()));
+        assert_eq!((Pt2::new([1, 1]), Pt2::new([8, 9])), bounding_box(&data()));
+    }
+
+    #[test]
+    fn test_area_map() {
+        let points = data();
+        let map = AreaMap::new(&points);
+        let owner = |x: i64, y: i64| map.owners[map.grid.index_of(&Pt2::new([x, y]))];
+        assert_eq!([8, 9], map.grid.dims);
+        assert_eq!(Owner::Nearest(0), owner(1, 1));
+        assert_eq!(Owner::Nearest(3), owner(3, 3));
+        assert_eq!(Owner::Tie, owner(1, 4));
+        assert_eq!(Owner::Tie, owner(5, 1));
+        assert_eq!(
+            vec![None, None, None, Some(9), Some(17), None],
+            map.areas(points.len())
+        );
     }
 
     #[test]
     fn test_largest_area() {
-        assert_eq!(17, largest_area(&data()));
+        assert_eq!(Some(17), largest_area(&data()));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let (a, b) = (Pt2::new([1, 1]), Pt2::new([4, -3]));
+        assert_eq!(7, Manhattan.distance(&a, &b));
+        assert_eq!(4, Chebyshev.distance(&a, &b));
+        assert_eq!(25, SquaredEuclidean.distance(&a, &b));
+        assert_eq!(5.0, Euclidean.distance(&a, &b));
+    }
+
+    #[test]
+    fn test_largest_area_with() {
+        let points = data();
+        assert_eq!(Some(17), largest_area_with(&points, &Manhattan));
+        let (min, max) = bounding_box(&points);
+        assert_eq!(
+            AreaMap::new(&points).owners,
+            AreaMap::with_metric(&points, &Manhattan, &min, &max).owners
+        );
+        assert_eq!(Some(10), largest_area_with(&points, &Chebyshev));
+        assert_eq!(Some(16), largest_area_with(&points, &Euclidean));
+        assert_eq!(Some(16), largest_area_with(&points, &SquaredEuclidean));
+
+        let pair = [Pt2::new([0, 0]), Pt2::new([3, 1])];
+        assert_eq!(None, largest_area_with(&pair, &Manhattan));
+        assert_eq!(None, largest_area(&pair));
+        assert_eq!(None, largest_area_with(&points[..3], &Euclidean));
+    }
+
+    // relies on the default areas
+    struct Taxicab;
+
+    impl Metric for Taxicab {
+        type Distance = u64;
+
+        fn distance<const N: usize>(&self, a: &Point<N>, b: &Point<N>) -> u64 {
+            Manhattan.distance(a, b)
+        }
+
+        fn reach(&self, threshold: u64, point_count: usize) -> u64 {
+            Manhattan.reach(threshold, point_count)
+        }
+    }
+
+    #[test]
+    fn test_default_areas() {
+        let points = data();
+        assert_eq!(Manhattan.areas(&points), Taxicab.areas(&points));
+        assert_eq!(Some(17), largest_area_with(&points, &Taxicab));
+    }
+
+    #[test]
+    fn test_chebyshev_areas() {
+        // (5, 0) is on the edge of the bounding box but its region is cut off below it
+        let points: Vec<Pt2> = vec![[0, 0], [3, 1], [8, 0], [5, 0], [6, 3]]
+            .into_iter()
+            .map(Pt2::new)
+            .collect();
+        assert_eq!(vec![None; 5], Manhattan.areas(&points));
+        assert_eq!(
+            vec![None, None, None, Some(11), None],
+            Chebyshev.areas(&points)
+        );
+    }
+
+    #[test]
+    fn test_euclidean_areas() {
+        // (1, 5) reaches the edge of the bounding box but is inside the convex hull
+        let points: Vec<Pt2> = vec![[0, 0], [0, 10], [1, 5], [10, 5]]
+            .into_iter()
+            .map(Pt2::new)
+            .collect();
+        assert_eq!(vec![None, None, Some(59), None], Euclidean.areas(&points));
+
+        // a thin region running far outside the bounding box
+        let points: Vec<Pt2> = vec![[9, 0], [9, 6], [11, 11], [10, 6]]
+            .into_iter()
+            .map(Pt2::new)
+            .collect();
+        assert_eq!(vec![None, None, None, Some(471)], Euclidean.areas(&points));
+        assert_eq!(SquaredEuclidean.areas(&points), Euclidean.areas(&points));
+
+        let twice = [Pt2::new([0, 0]), Pt2::new([0, 0]), Pt2::new([2, 1])];
+        assert_eq!(vec![Some(0), Some(0), None], Euclidean.areas(&twice));
+    }
+
+    #[test]
+    fn test_region_size_with() {
+        let points = data();
+        assert_eq!(16, region_size_with(&points, 32, &Manhattan));
+        assert_eq!(25, region_size_with(&points, 26.0, &Euclidean));
+        assert_eq!(
+            region_size(&points, 1000),
+            Some(u128::from(region_size_with(&points, 1000, &Manhattan)))
+        );
+
+        let origin = [Pt2::new([0, 0])];
+        assert_eq!(305, region_size_with(&origin, 10.0, &Euclidean));
+        assert_eq!(317, region_size_with(&origin, 101, &SquaredEuclidean));
+        assert_eq!(25, region_size_with(&origin, 3, &Chebyshev));
+        assert_eq!(0, region_size_with(&origin, 0, &Chebyshev));
+    }
+
+    #[test]
+    fn test_reach() {
+        assert_eq!(5, Manhattan.reach(32, 6));
+        assert_eq!(0, Manhattan.reach(0, 6));
+        assert_eq!(4, Chebyshev.reach(13, 3));
+        assert_eq!(2, SquaredEuclidean.reach(13, 3));
+        assert_eq!(1, SquaredEuclidean.reach(12, 3));
+        assert_eq!(3, Euclidean.reach(12.0, 3));
+        assert_eq!(4, Euclidean.reach(12.5, 3));
+        assert_eq!(0, Euclidean.reach(0.0, 3));
+    }
+
+    #[test]
+    fn test_axis_distance_sums() {
+        let sums = axis_distance_sums(vec![1, 1, 8, 3, 5, 8].into_iter(), 30);
+        assert_eq!(vec![16, 16, 16, 18, 18, 20, 20, 22], sums.inside);
+        assert_eq!(
+            [
+                Progression {
+                    start: 26,
+                    step: 6,
+                    len: 1
+                },
+                Progression {
+                    start: 28,
+                    step: 6,
+                    len: 1
+                }
+            ],
+            sums.tails
+        );
+    }
+
+    #[test]
+    fn test_region_color() {
+        assert_eq!((255, 0, 0), region_color(0));
+        assert_ne!(region_color(1), region_color(2));
+    }
+
+    #[test]
+    fn test_render_ppm() {
+        let image = render_ppm(&data(), 32);
+        // the safe region may reach 5 cells past the bounding box
+        let header = b"P6\n18 19\n255\n";
+        assert_eq!(&header[..], &image[..header.len()]);
+        assert_eq!(header.len() + 18 * 19 * 3, image.len());
+
+        let pixel = |x: i64, y: i64| {
+            let offset = header.len() + 3 * ((y + 4) * 18 + (x + 4)) as usize;
+            (image[offset], image[offset + 1], image[offset + 2])
+        };
+        assert_eq!((255, 255, 255), pixel(1, 1));
+        assert_eq!((0, 0, 0), pixel(1, 4));
+        assert_eq!((85, 0, 0), pixel(2, 1));
+        assert_eq!((85, 0, 0), pixel(-4, -4));
+        assert_eq!((128, 128, 128), pixel(0, 0));
+        assert_eq!((128, 128, 128), pixel(9, 5));
+        let (r, g, b) = region_color(4);
+        assert_eq!((r / 2 + 128, g / 2 + 128, b / 2 + 128), pixel(5, 4));
+    }
+
+    fn plain_lines(ansi: &str) -> Vec<String> {
+        ansi.lines()
+            .map(|line| {
+                line.split('\x1b')
+                    .map(|part| part.splitn(2, 'm').last().unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        let ansi = render_ansi(&data(), 32);
+        assert_eq!(
+            plain_lines(&ansi),
+            vec![
+                "+++++++++.++++++++",
+                "+++++++++.++++++++",
+                "+++++++++.++++++++",
+                "+++++++++.++++++++",
+                "++++┌────────┐++++",
+                "++++│@+++.+++│++++",
+                "++++│++++++++│++++",
+                "++++│++###++@│++++",
+                "....│.#@###++│++++",
+                "++++│+###@#++│++++",
+                "++++│@+###++.│....",
+                "++++│++.+++++│++++",
+                "++++│++.+++++│++++",
+                "++++│++.++++@│++++",
+                "++++└────────┘++++",
+                "+++++++.++++++++++",
+                "+++++++.++++++++++",
+                "+++++++.++++++++++",
+                "+++++++.++++++++++",
+            ]
+        );
+        assert!(ansi.lines().all(|line| line.ends_with("\x1b[0m")));
+    }
+
+    #[test]
+    fn test_render_ansi_beyond_bounding_box() {
+        let ansi = render_ansi(&[Pt2::new([0, 0])], 3);
+        assert_eq!(
+            plain_lines(&ansi),
+            vec!["++#++", "+┌─┐+", "#│@│#", "+└─┘+", "++#++"]
+        );
     }
 
     #[test]
     fn test_region_size() {
-        assert_eq!(16, region_size(&data(), 32));
+        let origin = [Pt2::new([0, 0])];
+        assert_eq!(Some(16), region_size(&data(), 32));
+        assert_eq!(Some(0), region_size(&data(), 0));
+        assert_eq!(Some(1), region_size(&origin, 1));
+        assert_eq!(Some(5), region_size(&origin, 2));
+        assert_eq!(Some(1_998_001), region_size(&origin, 1000));
+        assert_eq!(
+            Some(1_999_999_998_000_000_001),
+            region_size(&origin, 1_000_000_000)
+        );
+        // just past u64::MAX
+        assert_eq!(
+            Some(31_999_999_992_000_000_001),
+            region_size(&origin, 4_000_000_000)
+        );
+        assert_eq!(None, region_size(&origin, u64::MAX));
+        assert_eq!(Some(555_555_544_444_416), region_size(&data(), 100_000_000));
+        assert_eq!(
+            Some(u128::from(region_size_with(&data(), 100, &Manhattan))),
+            region_size(&data(), 100)
+        );
     }
 }
@@ -1,144 +1,841 @@
-use std::collections::{HashMap, HashSet};
+use regex::Regex;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::str::FromStr;
 
-#[derive(Clone)]
-struct Task {
-    id: u8,
-    deps: HashSet<u8>,
-    eta: u32,
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct Rule {
+    prereq: String,
+    task: String,
 }
 
-impl Task {
-    fn new(id: u8) -> Task {
-        Task {
-            id,
-            deps: HashSet::new(),
-            eta: 0,
+impl Rule {
+    pub fn new(prereq: &str, task: &str) -> Rule {
+        Rule {
+            prereq: prereq.to_owned(),
+            task: task.to_owned(),
         }
     }
 
-    fn with_dep(id: u8, dep: u8) -> Task {
-        let mut deps = HashSet::new();
-        deps.insert(dep);
-        Task { id, deps, eta: 0 }
+    pub fn prereq(&self) -> &str {
+        &self.prereq
+    }
+
+    pub fn task(&self) -> &str {
+        &self.task
     }
 }
 
-fn make_task_map(rules: &[&str]) -> HashMap<u8, Task> {
-    let mut task_map: HashMap<u8, Task> = HashMap::new();
+#[derive(PartialEq, Debug)]
+pub struct ParseRuleError();
 
-    rules
-        .iter()
-        .map(|rule| {
-            let mut r = rule.bytes();
-            let prereq = r.nth(5).unwrap();
-            let task = r.nth(30).unwrap();
-            (task, prereq)
-        })
-        .fold(&mut task_map, |task_map, (task, prereq)| {
-            task_map
-                .entry(task)
-                .and_modify(|task| {
-                    task.deps.insert(prereq);
-                })
-                .or_insert_with(|| Task::with_dep(task, prereq));
-            task_map.entry(prereq).or_insert_with(|| Task::new(prereq));
-            task_map
-        });
+impl FromStr for Rule {
+    type Err = ParseRuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Step C must be finished before step A can begin.
+        // C -> A
+        lazy_static! {
+            static ref STEP_RE: Regex =
+                Regex::new(r"^Step (\S+) must be finished before step (\S+) can begin\.$").unwrap();
+            static ref EDGE_RE: Regex = Regex::new(r"^\s*(\S+)\s*->\s*(\S+)\s*$").unwrap();
+        }
+
+        let s = s.trim();
+        let caps = STEP_RE
+            .captures(s)
+            .or_else(|| EDGE_RE.captures(s))
+            .ok_or(ParseRuleError())?;
+        Ok(Rule::new(&caps[1], &caps[2]))
+    }
+}
 
-    task_map
+#[derive(PartialEq, Debug)]
+pub enum TaskGraphError {
+    UnknownRule(String),
+    DuplicateRule(Rule),
+    Cycle(Vec<String>),
+    NotALetter(String),
 }
 
-pub fn task_execution_order(rules: &[&str]) -> String {
-    let mut remaining_tasks: Vec<Task> =
-        make_task_map(rules).drain().map(|(_, task)| task).collect();
-    let mut pending_tasks: Vec<Task> = Vec::new();
-    let mut ordered_tasks = String::new();
+#[derive(Clone, Default)]
+struct Task {
+    deps: HashSet<String>,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Policy {
+    Alphabetical,
+    LongestFirst,
+    MostDependentsFirst,
+    CriticalPathFirst,
+    Priorities(HashMap<String, i64>),
+}
 
-    while find_pending_tasks(&mut pending_tasks, &mut remaining_tasks) {
-        ordered_tasks.push(char::from(run_first_pending_task(
-            &mut pending_tasks,
-            &mut remaining_tasks,
-        )));
+impl Policy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Policy::Alphabetical => "alphabetical",
+            Policy::LongestFirst => "longest first",
+            Policy::MostDependentsFirst => "most dependents first",
+            Policy::CriticalPathFirst => "critical path first",
+            Policy::Priorities(_) => "priorities",
+        }
     }
 
-    ordered_tasks
+    pub fn builtin() -> Vec<Policy> {
+        vec![
+            Policy::Alphabetical,
+            Policy::LongestFirst,
+            Policy::MostDependentsFirst,
+            Policy::CriticalPathFirst,
+        ]
+    }
 }
 
-pub fn task_duration(rules: &[&str], worker_count: usize, extra_task_duration: u32) -> u32 {
-    let mut remaining_tasks: Vec<Task> =
-        make_task_map(rules).drain().map(|(_, task)| task).collect();
-    let mut pending_tasks: Vec<Task> = Vec::new();
-    let mut running_tasks: Vec<Task> = Vec::new();
-    let mut seconds = 0u32;
+type PendingTasks = BinaryHeap<(i64, Reverse<usize>)>;
+
+// Tasks indexed in alphabetical order; pending tasks are picked by highest
+// priority first, ties broken by smaller index.
+struct TaskIndex<'a> {
+    names: Vec<&'a str>,
+    dependents: Vec<Vec<usize>>,
+    dep_counts: Vec<usize>,
+    priorities: Vec<i64>,
+}
 
-    while !(running_tasks.is_empty() && pending_tasks.is_empty() && remaining_tasks.is_empty()) {
-        find_pending_tasks(&mut pending_tasks, &mut remaining_tasks);
-        while running_tasks.len() != worker_count && !pending_tasks.is_empty() {
-            start_first_pending_task(&mut pending_tasks, &mut running_tasks, extra_task_duration);
+impl<'a> TaskIndex<'a> {
+    fn new(graph: &'a TaskGraph) -> TaskIndex<'a> {
+        let names = graph.task_names();
+        let indices: HashMap<&str, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(index, &name)| (name, index))
+            .collect();
+
+        let mut dependents = vec![Vec::new(); names.len()];
+        let mut dep_counts = vec![0; names.len()];
+        for (index, name) in names.iter().enumerate() {
+            for dep in &graph.tasks[*name].deps {
+                dependents[indices[dep.as_str()]].push(index);
+            }
+            dep_counts[index] = graph.tasks[*name].deps.len();
         }
 
-        seconds += 1;
-        running_tasks.iter_mut().for_each(|task| {
-            task.eta -= 1;
-        });
-        let task_ids: Vec<u8> = running_tasks
+        TaskIndex {
+            priorities: vec![0; names.len()],
+            names,
+            dependents,
+            dep_counts,
+        }
+    }
+
+    fn with_policy<F>(graph: &'a TaskGraph, policy: &Policy, task_duration: F) -> TaskIndex<'a>
+    where
+        F: Fn(&str) -> u32,
+    {
+        let mut index = TaskIndex::new(graph);
+        index.priorities = match policy {
+            Policy::Alphabetical => vec![0; index.names.len()],
+            Policy::LongestFirst => index
+                .names
+                .iter()
+                .map(|name| i64::from(task_duration(name)))
+                .collect(),
+            Policy::MostDependentsFirst => index
+                .dependents
+                .iter()
+                .map(|dependents| dependents.len() as i64)
+                .collect(),
+            Policy::CriticalPathFirst => {
+                let critical_path = graph.critical_path(task_duration);
+                critical_path
+                    .timings()
+                    .iter()
+                    .map(|timing| i64::from(critical_path.min_duration() - timing.latest_start()))
+                    .collect()
+            }
+            Policy::Priorities(priorities) => index
+                .names
+                .iter()
+                .map(|name| priorities.get(*name).cloned().unwrap_or(0))
+                .collect(),
+        };
+        index
+    }
+
+    fn initial_tasks(&self) -> PendingTasks {
+        (0..self.names.len())
+            .filter(|&index| self.dep_counts[index] == 0)
+            .map(|index| (self.priorities[index], Reverse(index)))
+            .collect()
+    }
+
+    fn topological_order(&self) -> Vec<usize> {
+        let mut dep_counts = self.dep_counts.clone();
+        let mut pending_tasks = self.initial_tasks();
+        let mut order = Vec::with_capacity(self.names.len());
+
+        while let Some((_, Reverse(task))) = pending_tasks.pop() {
+            order.push(task);
+            self.finish_task(task, &mut dep_counts, &mut pending_tasks);
+        }
+
+        order
+    }
+
+    fn finish_task(
+        &self,
+        index: usize,
+        dep_counts: &mut [usize],
+        pending_tasks: &mut PendingTasks,
+    ) {
+        for &dependent in &self.dependents[index] {
+            dep_counts[dependent] -= 1;
+            if dep_counts[dependent] == 0 {
+                pending_tasks.push((self.priorities[dependent], Reverse(dependent)));
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum DotHighlight {
+    None,
+    ExecutionOrder,
+    CriticalPath,
+}
+
+pub struct TaskGraph {
+    tasks: HashMap<String, Task>,
+}
+
+impl TaskGraph {
+    pub fn new(rules: &[Rule]) -> Result<TaskGraph, TaskGraphError> {
+        let mut tasks: HashMap<String, Task> = HashMap::new();
+        let mut known_rules: HashSet<&Rule> = HashSet::new();
+
+        for rule in rules {
+            if !known_rules.insert(rule) {
+                return Err(TaskGraphError::DuplicateRule(rule.clone()));
+            }
+            tasks
+                .entry(rule.task.clone())
+                .or_default()
+                .deps
+                .insert(rule.prereq.clone());
+            tasks.entry(rule.prereq.clone()).or_default();
+        }
+
+        match find_cycle(&tasks) {
+            Some(cycle) => Err(TaskGraphError::Cycle(cycle)),
+            None => Ok(TaskGraph { tasks }),
+        }
+    }
+
+    pub fn parse(rules: &[&str]) -> Result<TaskGraph, TaskGraphError> {
+        let rules = rules
             .iter()
-            .filter(|task| task.eta == 0)
-            .map(|task| task.id)
+            .map(|rule| {
+                rule.parse()
+                    .map_err(|_| TaskGraphError::UnknownRule(rule.to_string()))
+            })
+            .collect::<Result<Vec<Rule>, _>>()?;
+        TaskGraph::new(&rules)
+    }
+
+    pub fn task_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.tasks.keys().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn execution_order(&self) -> Vec<String> {
+        self.execution_order_with(&Policy::Alphabetical, |_| 0)
+    }
+
+    pub fn execution_order_with<F>(&self, policy: &Policy, task_duration: F) -> Vec<String>
+    where
+        F: Fn(&str) -> u32,
+    {
+        let index = TaskIndex::with_policy(self, policy, task_duration);
+        index
+            .topological_order()
+            .into_iter()
+            .map(|task| index.names[task].to_owned())
+            .collect()
+    }
+
+    pub fn duration<F>(&self, worker_count: usize, task_duration: F) -> u32
+    where
+        F: Fn(&str) -> u32,
+    {
+        self.schedule(worker_count, task_duration).duration()
+    }
+
+    pub fn schedule<F>(&self, worker_count: usize, task_duration: F) -> Schedule
+    where
+        F: Fn(&str) -> u32,
+    {
+        self.schedule_with(worker_count, &Policy::Alphabetical, task_duration)
+    }
+
+    pub fn schedule_with<F>(
+        &self,
+        worker_count: usize,
+        policy: &Policy,
+        task_duration: F,
+    ) -> Schedule
+    where
+        F: Fn(&str) -> u32,
+    {
+        let worker_count = worker_count.max(1);
+        let index = TaskIndex::with_policy(self, policy, &task_duration);
+        let mut dep_counts = index.dep_counts.clone();
+        let mut pending_tasks = index.initial_tasks();
+        let mut idle_workers: BinaryHeap<Reverse<usize>> = (0..worker_count).map(Reverse).collect();
+        let mut running_tasks: BinaryHeap<Reverse<(u32, usize, usize)>> = BinaryHeap::new();
+        let mut scheduled_tasks: Vec<ScheduledTask> = Vec::with_capacity(index.names.len());
+        let mut seconds = 0u32;
+
+        loop {
+            while !idle_workers.is_empty() && !pending_tasks.is_empty() {
+                let Reverse(worker) = idle_workers.pop().unwrap();
+                let (_, Reverse(task)) = pending_tasks.pop().unwrap();
+                let name = index.names[task];
+                let end = seconds + task_duration(name);
+                running_tasks.push(Reverse((end, task, worker)));
+                scheduled_tasks.push(ScheduledTask {
+                    worker: worker + 1,
+                    task: name.to_owned(),
+                    start: seconds,
+                    end,
+                });
+            }
+
+            match running_tasks.peek() {
+                Some(&Reverse((end, _, _))) => seconds = end,
+                None => break,
+            }
+            while let Some(&Reverse((end, task, worker))) = running_tasks.peek() {
+                if end != seconds {
+                    break;
+                }
+                running_tasks.pop();
+                idle_workers.push(Reverse(worker));
+                index.finish_task(task, &mut dep_counts, &mut pending_tasks);
+            }
+        }
+
+        Schedule {
+            worker_count,
+            tasks: scheduled_tasks,
+        }
+    }
+
+    pub fn critical_path<F>(&self, task_duration: F) -> CriticalPath
+    where
+        F: Fn(&str) -> u32,
+    {
+        let index = TaskIndex::new(self);
+        let order = index.topological_order();
+        let durations: Vec<u32> = index.names.iter().map(|name| task_duration(name)).collect();
+
+        let mut earliest_starts = vec![0u32; index.names.len()];
+        for &task in &order {
+            let finish = earliest_starts[task] + durations[task];
+            for &dependent in &index.dependents[task] {
+                earliest_starts[dependent] = earliest_starts[dependent].max(finish);
+            }
+        }
+        let min_duration = (0..index.names.len())
+            .map(|task| earliest_starts[task] + durations[task])
+            .max()
+            .unwrap_or(0);
+
+        let mut latest_starts = vec![0u32; index.names.len()];
+        for &task in order.iter().rev() {
+            let finish = index.dependents[task]
+                .iter()
+                .map(|&dependent| latest_starts[dependent])
+                .min()
+                .unwrap_or(min_duration);
+            latest_starts[task] = finish - durations[task];
+        }
+
+        let is_critical = |task: usize| earliest_starts[task] == latest_starts[task];
+        let mut path = Vec::new();
+        let mut next =
+            (0..index.names.len()).find(|&task| index.dep_counts[task] == 0 && is_critical(task));
+        while let Some(task) = next {
+            path.push(index.names[task].to_owned());
+            let finish = earliest_starts[task] + durations[task];
+            next = index.dependents[task]
+                .iter()
+                .cloned()
+                .filter(|&dependent| is_critical(dependent) && earliest_starts[dependent] == finish)
+                .min();
+        }
+
+        let timings = (0..index.names.len())
+            .map(|task| TaskTiming {
+                task: index.names[task].to_owned(),
+                duration: durations[task],
+                earliest_start: earliest_starts[task],
+                latest_start: latest_starts[task],
+            })
             .collect();
-        task_ids.iter().for_each(|&task_id| {
-            finish_task(task_id, &mut running_tasks, &mut remaining_tasks);
-        });
+
+        CriticalPath {
+            timings,
+            path,
+            min_duration,
+        }
+    }
+
+    pub fn useful_worker_count<F>(&self, task_duration: F) -> usize
+    where
+        F: Fn(&str) -> u32,
+    {
+        let min_duration = self.critical_path(&task_duration).min_duration();
+        (1..self.tasks.len())
+            .find(|&worker_count| self.duration(worker_count, &task_duration) == min_duration)
+            .unwrap_or_else(|| self.tasks.len().max(1))
+    }
+
+    pub fn compare_policies<F>(
+        &self,
+        worker_count: usize,
+        policies: &[Policy],
+        task_duration: F,
+    ) -> Vec<(&'static str, u32)>
+    where
+        F: Fn(&str) -> u32,
+    {
+        policies
+            .iter()
+            .map(|policy| {
+                let schedule = self.schedule_with(worker_count, policy, &task_duration);
+                (policy.name(), schedule.duration())
+            })
+            .collect()
+    }
+
+    pub fn to_dot<F>(&self, highlight: DotHighlight, task_duration: F) -> String
+    where
+        F: Fn(&str) -> u32,
+    {
+        let names = self.task_names();
+        let order: HashMap<String, usize> = match highlight {
+            DotHighlight::ExecutionOrder => self
+                .execution_order()
+                .into_iter()
+                .enumerate()
+                .map(|(i, name)| (name, i + 1))
+                .collect(),
+            _ => HashMap::new(),
+        };
+        let path: Vec<String> = match highlight {
+            DotHighlight::CriticalPath => self.critical_path(&task_duration).path().to_vec(),
+            _ => Vec::new(),
+        };
+        let critical_edges: HashSet<(&str, &str)> = path
+            .windows(2)
+            .map(|pair| (pair[0].as_str(), pair[1].as_str()))
+            .collect();
+
+        let mut dot = String::from("digraph steps {\n    rankdir=LR;\n");
+        for &name in &names {
+            let position = match order.get(name) {
+                Some(position) => format!("{}. ", position),
+                None => String::new(),
+            };
+            let style = if path.iter().any(|task| task == name) {
+                ", color=red, penwidth=2"
+            } else {
+                ""
+            };
+            dot += &format!(
+                "    {} [label=\"{}{}\\n{}s\"{}];\n",
+                dot_string(name),
+                position,
+                dot_escape(name),
+                task_duration(name),
+                style
+            );
+        }
+
+        let mut edges: Vec<(&str, &str)> = names
+            .iter()
+            .flat_map(|&name| {
+                self.tasks[name]
+                    .deps
+                    .iter()
+                    .map(move |dep| (dep.as_str(), name))
+            })
+            .collect();
+        edges.sort_unstable();
+        for (prereq, task) in edges {
+            let style = if critical_edges.contains(&(prereq, task)) {
+                " [color=red, penwidth=2]"
+            } else {
+                ""
+            };
+            dot += &format!(
+                "    {} -> {}{};\n",
+                dot_string(prereq),
+                dot_string(task),
+                style
+            );
+        }
+        dot += "}\n";
+
+        dot
+    }
+}
+
+fn dot_string(s: &str) -> String {
+    format!("\"{}\"", dot_escape(s))
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct TaskTiming {
+    task: String,
+    duration: u32,
+    earliest_start: u32,
+    latest_start: u32,
+}
+
+impl TaskTiming {
+    pub fn task(&self) -> &str {
+        &self.task
+    }
+
+    pub fn duration(&self) -> u32 {
+        self.duration
+    }
+
+    pub fn earliest_start(&self) -> u32 {
+        self.earliest_start
+    }
+
+    pub fn latest_start(&self) -> u32 {
+        self.latest_start
+    }
+
+    pub fn earliest_finish(&self) -> u32 {
+        self.earliest_start + self.duration
+    }
+
+    pub fn latest_finish(&self) -> u32 {
+        self.latest_start + self.duration
+    }
+
+    pub fn slack(&self) -> u32 {
+        self.latest_start - self.earliest_start
+    }
+
+    pub fn is_critical(&self) -> bool {
+        self.slack() == 0
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct CriticalPath {
+    timings: Vec<TaskTiming>,
+    path: Vec<String>,
+    min_duration: u32,
+}
+
+impl CriticalPath {
+    pub fn timings(&self) -> &[TaskTiming] {
+        &self.timings
+    }
+
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    pub fn min_duration(&self) -> u32 {
+        self.min_duration
+    }
+}
+
+// Workers are numbered from 1.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ScheduledTask {
+    worker: usize,
+    task: String,
+    start: u32,
+    end: u32,
+}
+
+impl ScheduledTask {
+    pub fn worker(&self) -> usize {
+        self.worker
+    }
+
+    pub fn task(&self) -> &str {
+        &self.task
+    }
+
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    pub fn end(&self) -> u32 {
+        self.end
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Schedule {
+    worker_count: usize,
+    tasks: Vec<ScheduledTask>,
+}
+
+impl Schedule {
+    pub fn worker_count(&self) -> usize {
+        self.worker_count
+    }
+
+    pub fn tasks(&self) -> &[ScheduledTask] {
+        &self.tasks
+    }
+
+    pub fn duration(&self) -> u32 {
+        self.tasks.iter().map(|task| task.end).max().unwrap_or(0)
+    }
+
+    /// One row per task in start order, marking the columns it runs in.
+    /// Tasks always cover at least one column, even when shorter than it.
+    pub fn to_gantt(&self, seconds_per_column: u32) -> String {
+        let seconds_per_column = seconds_per_column.max(1);
+        let columns = self.duration().div_ceil(seconds_per_column) as usize;
+        let task_width = self
+            .tasks
+            .iter()
+            .map(|task| task.task.chars().count())
+            .max()
+            .unwrap_or(0);
+        let worker_width = format!("Worker {}", self.worker_count).len();
+
+        self.tasks
+            .iter()
+            .map(|task| {
+                let first = (task.start / seconds_per_column) as usize;
+                let last = (task.end.div_ceil(seconds_per_column) as usize).max(first + 1);
+                let row: String = (0..columns.max(last))
+                    .map(|column| {
+                        if column >= first && column < last {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                format!(
+                    "{:<task_width$}  {:<worker_width$}  {}",
+                    task.task,
+                    format!("Worker {}", task.worker),
+                    row,
+                    task_width = task_width,
+                    worker_width = worker_width
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 
-    while find_pending_tasks(&mut pending_tasks, &mut remaining_tasks) {
-        run_first_pending_task(&mut pending_tasks, &mut remaining_tasks);
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("worker,task,start,end\n");
+        for task in &self.tasks {
+            csv += &format!(
+                "{},{},{},{}\n",
+                task.worker,
+                csv_field(&task.task),
+                task.start,
+                task.end
+            );
+        }
+        csv
     }
 
-    seconds
+    pub fn to_json(&self) -> String {
+        let tasks = self
+            .tasks
+            .iter()
+            .map(|task| {
+                format!(
+                    "{{\"worker\":{},\"task\":{},\"start\":{},\"end\":{}}}",
+                    task.worker,
+                    json_string(&task.task),
+                    task.start,
+                    task.end
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"workers\":{},\"duration\":{},\"tasks\":[{}]}}",
+            self.worker_count,
+            self.duration(),
+            tasks.join(",")
+        )
+    }
 }
 
-fn find_pending_tasks(pending_tasks: &mut Vec<Task>, remaining_tasks: &mut Vec<Task>) -> bool {
-    let (mut pending, mut remaining): (Vec<Task>, Vec<Task>) = remaining_tasks
-        .clone()
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            c if (c as u32) < 0x20 => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+fn find_cycle(tasks: &HashMap<String, Task>) -> Option<Vec<String>> {
+    let sorted_deps = |name: &str| {
+        let mut deps: Vec<&str> = tasks[name].deps.iter().map(|dep| dep.as_str()).collect();
+        deps.sort_unstable_by(|a, b| b.cmp(a));
+        deps
+    };
+
+    let mut names: Vec<&str> = tasks.keys().map(|name| name.as_str()).collect();
+    names.sort_unstable();
+
+    let mut visits: HashMap<&str, Visit> = HashMap::new();
+    for name in names {
+        if visits.contains_key(name) {
+            continue;
+        }
+
+        visits.insert(name, Visit::InProgress);
+        let mut path: Vec<(&str, Vec<&str>)> = vec![(name, sorted_deps(name))];
+        while let Some((name, deps)) = path.last_mut() {
+            let name = *name;
+            match deps.pop() {
+                Some(dep) => match visits.get(dep) {
+                    Some(Visit::InProgress) => {
+                        let start = path.iter().position(|&(name, _)| name == dep).unwrap();
+                        let mut cycle: Vec<String> = path[start..]
+                            .iter()
+                            .rev()
+                            .map(|(name, _)| name.to_string())
+                            .collect();
+                        let first = (0..cycle.len()).min_by_key(|&i| &cycle[i]).unwrap();
+                        cycle.rotate_left(first);
+                        return Some(cycle);
+                    }
+                    Some(Visit::Done) => {}
+                    None => {
+                        visits.insert(dep, Visit::InProgress);
+                        path.push((dep, sorted_deps(dep)));
+                    }
+                },
+                None => {
+                    visits.insert(name, Visit::Done);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Steps named by a single letter take its position in the alphabet as duration.
+fn letter_duration(name: &str) -> Option<u32> {
+    match name.as_bytes() {
+        [c] if c.is_ascii_alphabetic() => Some(u32::from(c.to_ascii_uppercase() - b'A' + 1)),
+        _ => None,
+    }
+}
+
+fn parse_letter_steps(rules: &[&str]) -> Result<TaskGraph, TaskGraphError> {
+    let graph = TaskGraph::parse(rules)?;
+    let invalid = graph
+        .task_names()
         .into_iter()
-        .partition(|task| task.deps.is_empty());
-    remaining_tasks.clear();
-    remaining_tasks.append(&mut remaining);
-    pending_tasks.append(&mut pending);
-    pending_tasks.sort_unstable_by_key(|task| task.id);
-    !(pending_tasks.is_empty() && remaining_tasks.is_empty())
+        .find(|name| letter_duration(name).is_none());
+    match invalid {
+        Some(name) => Err(TaskGraphError::NotALetter(name.to_owned())),
+        None => Ok(graph),
+    }
 }
 
-fn run_first_pending_task(pending_tasks: &mut Vec<Task>, remaining_tasks: &mut Vec<Task>) -> u8 {
-    let task_id = pending_tasks.remove(0).id;
-    remaining_tasks.iter_mut().for_each(|task| {
-        task.deps.remove(&task_id);
-    });
-    task_id
+pub fn task_execution_order(rules: &[&str]) -> Result<String, TaskGraphError> {
+    Ok(TaskGraph::parse(rules)?.execution_order().concat())
 }
 
-fn start_first_pending_task(
-    pending_tasks: &mut Vec<Task>,
-    running_tasks: &mut Vec<Task>,
+pub fn task_duration(
+    rules: &[&str],
+    worker_count: usize,
     extra_task_duration: u32,
-) {
-    let mut task = pending_tasks.remove(0);
-    task.eta = extra_task_duration + u32::from(task.id - 64);
-    running_tasks.push(task);
+) -> Result<u32, TaskGraphError> {
+    Ok(task_schedule(rules, worker_count, extra_task_duration)?.duration())
 }
 
-fn finish_task(task_id: u8, running_tasks: &mut Vec<Task>, remaining_tasks: &mut Vec<Task>) {
-    let index = running_tasks
-        .iter()
-        .enumerate()
-        .find(|&(_, task)| task.id == task_id)
-        .unwrap()
-        .0;
-    running_tasks.remove(index);
-    remaining_tasks.iter_mut().for_each(|task| {
-        task.deps.remove(&task_id);
-    });
+pub fn task_schedule(
+    rules: &[&str],
+    worker_count: usize,
+    extra_task_duration: u32,
+) -> Result<Schedule, TaskGraphError> {
+    Ok(parse_letter_steps(rules)?.schedule(worker_count, |name| {
+        extra_task_duration + letter_duration(name).unwrap()
+    }))
+}
+
+pub fn task_critical_path(
+    rules: &[&str],
+    extra_task_duration: u32,
+) -> Result<CriticalPath, TaskGraphError> {
+    Ok(parse_letter_steps(rules)?
+        .critical_path(|name| extra_task_duration + letter_duration(name).unwrap()))
+}
+
+pub fn task_policy_durations(
+    rules: &[&str],
+    worker_count: usize,
+    extra_task_duration: u32,
+) -> Result<Vec<(&'static str, u32)>, TaskGraphError> {
+    Ok(
+        parse_letter_steps(rules)?.compare_policies(worker_count, &Policy::builtin(), |name| {
+            extra_task_duration + letter_duration(name).unwrap()
+        }),
+    )
+}
+
+pub fn task_graph_dot(
+    rules: &[&str],
+    extra_task_duration: u32,
+    highlight: DotHighlight,
+) -> Result<String, TaskGraphError> {
+    Ok(parse_letter_steps(rules)?.to_dot(highlight, |name| {
+        extra_task_duration + letter_duration(name).unwrap()
+    }))
 }
 
 #[cfg(test)]
@@ -156,32 +853,332 @@ mod tests {
         "Step F must be finished before step E can begin.",
     ];
 
-    fn make_set(chars: &str) -> HashSet<u8> {
-        HashSet::from_iter(chars.bytes())
+    fn make_set(names: &[&str]) -> HashSet<String> {
+        HashSet::from_iter(names.iter().map(|name| name.to_string()))
     }
 
     #[test]
-    fn test_make_task_map() {
-        let t = make_task_map(DATA);
+    fn test_parse_rule() {
+        assert_eq!(Ok(Rule::new("C", "A")), DATA[0].parse());
+        assert_eq!(Ok(Rule::new("fetch", "build")), "fetch -> build".parse());
+        assert_eq!(Ok(Rule::new("a.b", "c-d")), "  a.b->c-d ".parse());
         assert_eq!(
-            t.keys().cloned().collect::<HashSet<_>>(),
-            make_set("ABCDEF")
+            Ok(Rule::new("C", "A")),
+            "Step C must be finished before step A can begin.\r".parse()
+        );
+        assert_eq!(Err(ParseRuleError()), "Step C must be done".parse::<Rule>());
+        assert_eq!(Err(ParseRuleError()), "a -> b -> c".parse::<Rule>());
+    }
+
+    #[test]
+    fn test_task_graph() {
+        let g = TaskGraph::parse(DATA).unwrap();
+        assert_eq!(g.task_names(), vec!["A", "B", "C", "D", "E", "F"]);
+        assert_eq!(g.tasks["A"].deps, make_set(&["C"]));
+        assert_eq!(g.tasks["B"].deps, make_set(&["A"]));
+        assert_eq!(g.tasks["C"].deps, make_set(&[]));
+        assert_eq!(g.tasks["D"].deps, make_set(&["A"]));
+        assert_eq!(g.tasks["E"].deps, make_set(&["B", "D", "F"]));
+        assert_eq!(g.tasks["F"].deps, make_set(&["C"]));
+    }
+
+    #[test]
+    fn test_task_graph_names() {
+        let g = TaskGraph::parse(&[
+            "fetch -> compile",
+            "configure -> compile",
+            "compile -> test",
+            "compile -> package",
+        ])
+        .unwrap();
+        assert_eq!(
+            vec!["configure", "fetch", "compile", "package", "test"],
+            g.execution_order()
+        );
+
+        let durations: HashMap<&str, u32> = [
+            ("fetch", 30),
+            ("configure", 5),
+            ("compile", 120),
+            ("test", 60),
+            ("package", 10),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        assert_eq!(210, g.duration(2, |name| durations[name]));
+        assert_eq!(225, g.duration(1, |name| durations[name]));
+        assert_eq!(210, g.duration(5, |name| durations[name]));
+        assert_eq!(
+            3 * 86400 + 2 * 3600,
+            g.duration(2, |name| if name == "test" { 3 * 86400 } else { 3600 })
+        );
+    }
+
+    #[test]
+    fn test_task_graph_errors() {
+        assert_eq!(
+            Some(TaskGraphError::UnknownRule("C before A".to_owned())),
+            TaskGraph::parse(&["A -> B", "C before A"]).err()
+        );
+        assert_eq!(
+            Some(TaskGraphError::DuplicateRule(Rule::new("A", "B"))),
+            TaskGraph::parse(&["A -> B", "B -> C", "A -> B"]).err()
+        );
+        assert_eq!(
+            Some(TaskGraphError::Cycle(vec!["A".to_owned()])),
+            TaskGraph::parse(&["A -> A"]).err()
+        );
+        assert_eq!(
+            Some(TaskGraphError::Cycle(vec![
+                "B".to_owned(),
+                "C".to_owned(),
+                "D".to_owned()
+            ])),
+            TaskGraph::parse(&["A -> B", "B -> C", "C -> D", "D -> B", "D -> E"]).err()
+        );
+
+        let mut rules = DATA.to_vec();
+        rules.push("Step E must be finished before step C can begin.");
+        assert!(task_execution_order(&rules).is_err());
+        assert_eq!(
+            Err(TaskGraphError::Cycle(vec![
+                "A".to_owned(),
+                "B".to_owned(),
+                "E".to_owned(),
+                "C".to_owned()
+            ])),
+            task_duration(&rules, 2, 0)
         );
-        assert_eq!(t[&b'A'].deps, make_set("C"));
-        assert_eq!(t[&b'B'].deps, make_set("A"));
-        assert_eq!(t[&b'C'].deps, make_set(""));
-        assert_eq!(t[&b'D'].deps, make_set("A"));
-        assert_eq!(t[&b'E'].deps, make_set("BDF"));
-        assert_eq!(t[&b'F'].deps, make_set("C"));
     }
 
     #[test]
     fn test_task_execution_order() {
-        assert_eq!("CABDFE", task_execution_order(DATA));
+        assert_eq!(Ok("CABDFE".to_owned()), task_execution_order(DATA));
+    }
+
+    #[test]
+    fn test_task_schedule() {
+        let schedule = task_schedule(DATA, 2, 0).unwrap();
+        let tasks: Vec<_> = schedule
+            .tasks()
+            .iter()
+            .map(|task| (task.worker(), task.task(), task.start(), task.end()))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, "C", 0, 3),
+                (1, "A", 3, 4),
+                (2, "F", 3, 9),
+                (1, "B", 4, 6),
+                (1, "D", 6, 10),
+                (1, "E", 10, 15),
+            ],
+            tasks
+        );
+        assert_eq!(15, schedule.duration());
+
+        assert_eq!(
+            schedule.to_gantt(1),
+            [
+                "C  Worker 1  ###............",
+                "A  Worker 1  ...#...........",
+                "F  Worker 2  ...######......",
+                "B  Worker 1  ....##.........",
+                "D  Worker 1  ......####.....",
+                "E  Worker 1  ..........#####",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            schedule.to_gantt(4),
+            [
+                "C  Worker 1  #...",
+                "A  Worker 1  #...",
+                "F  Worker 2  ###.",
+                "B  Worker 1  .#..",
+                "D  Worker 1  .##.",
+                "E  Worker 1  ..##",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_schedule_export() {
+        let schedule = TaskGraph::parse(&["fetch -> \"build\",all"])
+            .unwrap()
+            .schedule(1, |_| 2);
+        assert_eq!(
+            "worker,task,start,end\n1,fetch,0,2\n1,\"\"\"build\"\",all\",2,4\n",
+            schedule.to_csv()
+        );
+        assert_eq!(
+            r#"{"workers":1,"duration":4,"tasks":[{"worker":1,"task":"fetch","start":0,"end":2},{"worker":1,"task":"\"build\",all","start":2,"end":4}]}"#,
+            schedule.to_json()
+        );
+        assert_eq!(
+            ["fetch        Worker 1  #.", "\"build\",all  Worker 1  .#"].join("\n"),
+            schedule.to_gantt(2)
+        );
+        assert_eq!(
+            ["fetch        Worker 1  #", "\"build\",all  Worker 1  #"].join("\n"),
+            schedule.to_gantt(10)
+        );
+    }
+
+    #[test]
+    fn test_task_critical_path() {
+        let critical_path = task_critical_path(DATA, 0).unwrap();
+        let timings: Vec<_> = critical_path
+            .timings()
+            .iter()
+            .map(|t| (t.task(), t.earliest_start(), t.latest_start(), t.slack()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("A", 3, 4, 1),
+                ("B", 4, 7, 3),
+                ("C", 0, 0, 0),
+                ("D", 4, 5, 1),
+                ("E", 9, 9, 0),
+                ("F", 3, 3, 0),
+            ],
+            timings
+        );
+        assert_eq!(vec!["C", "F", "E"], critical_path.path());
+        assert_eq!(14, critical_path.min_duration());
+        assert_eq!(Ok(14), task_duration(DATA, 6, 0));
+    }
+
+    #[test]
+    fn test_useful_worker_count() {
+        let g = TaskGraph::parse(DATA).unwrap();
+        assert_eq!(
+            3,
+            g.useful_worker_count(|name| letter_duration(name).unwrap())
+        );
+        assert_eq!(2, g.useful_worker_count(|_| 1));
+    }
+
+    #[test]
+    fn test_policies() {
+        let g = TaskGraph::parse(DATA).unwrap();
+        let order = |policy: &Policy| {
+            g.execution_order_with(policy, |name| letter_duration(name).unwrap())
+                .concat()
+        };
+        assert_eq!("CABDFE", order(&Policy::Alphabetical));
+        assert_eq!("CFADBE", order(&Policy::LongestFirst));
+        assert_eq!("CABDFE", order(&Policy::MostDependentsFirst));
+        assert_eq!("CFADBE", order(&Policy::CriticalPathFirst));
+
+        let priorities: HashMap<String, i64> = [("B".to_owned(), 5), ("D".to_owned(), -1)]
+            .iter()
+            .cloned()
+            .collect();
+        assert_eq!("CABFDE", order(&Policy::Priorities(priorities)));
+
+        let schedule = g.schedule_with(3, &Policy::CriticalPathFirst, |name| {
+            letter_duration(name).unwrap()
+        });
+        assert_eq!(14, schedule.duration());
+    }
+
+    #[test]
+    fn test_compare_policies() {
+        let g = TaskGraph::parse(&["A -> Z", "B -> Y", "C -> Y"]).unwrap();
+        let durations = |name: &str| match name {
+            "A" => 1,
+            "Z" => 10,
+            "Y" => 0,
+            _ => 5,
+        };
+        assert_eq!(
+            vec![
+                ("alphabetical", 15),
+                ("longest first", 16),
+                ("most dependents first", 15),
+                ("critical path first", 11),
+            ],
+            g.compare_policies(2, &Policy::builtin(), durations)
+        );
+    }
+
+    #[test]
+    fn test_task_policy_durations() {
+        assert_eq!(
+            Ok(vec![
+                ("alphabetical", 15),
+                ("longest first", 15),
+                ("most dependents first", 15),
+                ("critical path first", 15),
+            ]),
+            task_policy_durations(DATA, 2, 0)
+        );
+    }
+
+    #[test]
+    fn test_task_graph_dot() {
+        assert_eq!(
+            Ok([
+                "digraph steps {",
+                "    rankdir=LR;",
+                r#"    "A" [label="A\n1s"];"#,
+                r#"    "B" [label="B\n2s"];"#,
+                r#"    "C" [label="C\n3s"];"#,
+                r#"    "D" [label="D\n4s"];"#,
+                r#"    "E" [label="E\n5s"];"#,
+                r#"    "F" [label="F\n6s"];"#,
+                r#"    "A" -> "B";"#,
+                r#"    "A" -> "D";"#,
+                r#"    "B" -> "E";"#,
+                r#"    "C" -> "A";"#,
+                r#"    "C" -> "F";"#,
+                r#"    "D" -> "E";"#,
+                r#"    "F" -> "E";"#,
+                "}",
+                "",
+            ]
+            .join("\n")),
+            task_graph_dot(DATA, 0, DotHighlight::None)
+        );
+
+        let dot = task_graph_dot(DATA, 0, DotHighlight::ExecutionOrder).unwrap();
+        assert!(dot.contains(r#""C" [label="1. C\n3s"];"#));
+        assert!(dot.contains(r#""E" [label="6. E\n5s"];"#));
+
+        let dot = task_graph_dot(DATA, 0, DotHighlight::CriticalPath).unwrap();
+        assert!(dot.contains(r#""F" [label="F\n6s", color=red, penwidth=2];"#));
+        assert!(dot.contains(r#""A" [label="A\n1s"];"#));
+        assert!(dot.contains(r#""C" -> "F" [color=red, penwidth=2];"#));
+        assert!(dot.contains(r#""C" -> "A";"#));
+
+        let dot = TaskGraph::parse(&["say\"hi\" -> x"])
+            .unwrap()
+            .to_dot(DotHighlight::None, |_| 1);
+        assert!(dot.contains(r#""say\"hi\"" [label="say\"hi\"\n1s"];"#));
     }
 
     #[test]
     fn test_task_duration() {
-        assert_eq!(15, task_duration(DATA, 2, 0));
+        assert_eq!(Ok(15), task_duration(DATA, 2, 0));
+        assert_eq!(Ok(4), task_duration(&["a -> c"], 1, 0));
+        assert_eq!(
+            Err(TaskGraphError::NotALetter("step1".to_owned())),
+            task_duration(&["step1 -> step2"], 1, 0)
+        );
+        assert_eq!(
+            Some(TaskGraphError::NotALetter("fetch".to_owned())),
+            task_graph_dot(&["fetch -> A"], 0, DotHighlight::None).err()
+        );
+        assert!(task_critical_path(&["A -> a.b"], 0).is_err());
+        assert!(task_policy_durations(&["Ä -> A"], 2, 0).is_err());
+        assert_eq!(
+            Ok("fetchstep1".to_owned()),
+            task_execution_order(&["fetch -> step1"])
+        );
+        assert_eq!(Ok(21), task_duration(DATA, 1, 0));
+        assert_eq!(Ok(14), task_duration(DATA, 5, 0));
     }
 }
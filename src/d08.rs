@@ -1,66 +1,605 @@
-struct Node<'a> {
-    children: Vec<Node<'a>>,
-    metadata: &'a [u32],
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+#[derive(PartialEq, Debug)]
+pub enum ParseTreeError {
+    InvalidNumber,
+    Truncated,
+    TrailingData(usize),
+    InvalidMetadataIndex(u32),
 }
 
-impl<'a> Node<'a> {
-    fn new(data: &'a [u32]) -> Node<'a> {
-        let (node, _) = Node::from(data);
-        node
+impl From<ParseIntError> for ParseTreeError {
+    fn from(_error: ParseIntError) -> Self {
+        ParseTreeError::InvalidNumber
     }
+}
 
-    fn from(data: &'a [u32]) -> (Node<'a>, &'a [u32]) {
-        let children_len = data[0] as usize;
-        let metadata_len = data[1] as usize;
+#[derive(Default)]
+pub struct Node {
+    children: Vec<Node>,
+    metadata: Vec<u32>,
+}
 
-        let (children, data): (Vec<Node<'a>>, &'a [u32]) =
-            (0..children_len).fold((vec![], &data[2..]), |(mut children, data), _| {
-                let (node, data) = Node::from(data);
-                children.push(node);
-                (children, data)
-            });
-        let metadata = &data[..metadata_len];
+enum Visit<'a> {
+    Enter(&'a Node, usize),
+    Leave(&'a Node),
+}
+
+struct PartialNode {
+    children_left: usize,
+    metadata_len: usize,
+    children: Vec<Node>,
+}
+
+impl Node {
+    pub fn new(children: Vec<Node>, metadata: Vec<u32>) -> Node {
+        Node { children, metadata }
+    }
+
+    pub fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    pub fn children_mut(&mut self) -> &mut Vec<Node> {
+        &mut self.children
+    }
+
+    pub fn metadata(&self) -> &[u32] {
+        &self.metadata
+    }
 
-        (Node { children, metadata }, &data[metadata_len..])
+    pub fn metadata_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.metadata
     }
 
-    fn value(&self) -> u32 {
-        if self.children.is_empty() {
-            self.metadata.iter().sum()
+    pub fn from_numbers(data: &[u32]) -> Result<Node, ParseTreeError> {
+        let (node, data) = Node::from(data)?;
+        if data.is_empty() {
+            Ok(node)
         } else {
-            self.metadata.iter().fold(0, |acc, index| {
-                let index = (*index - 1) as usize;
-                if let Some(node) = self.children.get(index) {
-                    acc + node.value()
+            Err(ParseTreeError::TrailingData(data.len()))
+        }
+    }
+
+    fn from(data: &[u32]) -> Result<(Node, &[u32]), ParseTreeError> {
+        let mut data = data;
+        let mut stack: Vec<PartialNode> = Vec::new();
+
+        loop {
+            if data.len() < 2 {
+                return Err(ParseTreeError::Truncated);
+            }
+            let children_len = data[0] as usize;
+            let metadata_len = data[1] as usize;
+            data = &data[2..];
+            stack.push(PartialNode {
+                children_left: children_len,
+                metadata_len,
+                children: Vec::with_capacity(children_len.min(data.len() / 2)),
+            });
+
+            loop {
+                let partial = stack.last_mut().unwrap();
+                if partial.children_left != 0 {
+                    partial.children_left -= 1;
+                    break;
+                }
+
+                if data.len() < partial.metadata_len {
+                    return Err(ParseTreeError::Truncated);
+                }
+                let (metadata, rest) = data.split_at(partial.metadata_len);
+                data = rest;
+                if !partial.children.is_empty() && metadata.contains(&0) {
+                    return Err(ParseTreeError::InvalidMetadataIndex(0));
+                }
+
+                let partial = stack.pop().unwrap();
+                let node = Node {
+                    children: partial.children,
+                    metadata: metadata.to_vec(),
+                };
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Ok((node, data)),
+                }
+            }
+        }
+    }
+
+    /// Nodes in parse order: each node before its children.
+    pub fn pre_order(&self) -> PreOrder<'_> {
+        PreOrder { stack: vec![self] }
+    }
+
+    /// Each node after all of its children.
+    pub fn post_order(&self) -> PostOrder<'_> {
+        PostOrder {
+            stack: vec![(self, 0)],
+        }
+    }
+
+    /// Nodes level by level together with their depth, the root being at 0.
+    pub fn by_depth(&self) -> ByDepth<'_> {
+        let mut queue = VecDeque::new();
+        queue.push_back((0, self));
+        ByDepth { queue }
+    }
+
+    pub fn metadata_sum(&self) -> u64 {
+        self.pre_order()
+            .flat_map(|node| node.metadata.iter())
+            .map(|&metadata| u64::from(metadata))
+            .sum()
+    }
+
+    pub fn value(&self) -> u64 {
+        NodeValues::new(self).root_value()
+    }
+
+    /// Reports entering each node, with its index among its siblings,
+    /// before visiting its children and leaving it once they are visited.
+    fn walk<F: FnMut(Visit)>(&self, mut visit: F) {
+        visit(Visit::Enter(self, 0));
+        let mut stack = vec![(self, 0)];
+        while let Some((node, next_child)) = stack.last_mut() {
+            if let Some(child) = node.children.get(*next_child) {
+                visit(Visit::Enter(child, *next_child));
+                *next_child += 1;
+                stack.push((child, 0));
+            } else {
+                visit(Visit::Leave(node));
+                stack.pop();
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        self.walk(|visit| match visit {
+            Visit::Enter(node, index) => {
+                if index != 0 {
+                    json.push(',');
+                }
+                let metadata = node
+                    .metadata
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>();
+                json += &format!("{{\"metadata\":[{}],\"children\":[", metadata.join(","));
+            }
+            Visit::Leave(_) => json += "]}",
+        });
+        json
+    }
+}
+
+impl fmt::Display for Node {
+    /// Writes the tree back in the flat number format it is parsed from.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut numbers = Vec::new();
+        self.walk(|visit| match visit {
+            Visit::Enter(node, _) => {
+                numbers.push(node.children.len() as u32);
+                numbers.push(node.metadata.len() as u32);
+            }
+            Visit::Leave(node) => numbers.extend_from_slice(&node.metadata),
+        });
+        let numbers = numbers
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", numbers.join(" "))
+    }
+}
+
+impl fmt::Debug for Node {
+    /// Formats like the derived implementation, without recursing.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = String::new();
+        self.walk(|visit| match visit {
+            Visit::Enter(_, index) => {
+                if index != 0 {
+                    debug += ", ";
+                }
+                debug += "Node { children: [";
+            }
+            Visit::Leave(node) => debug += &format!("], metadata: {:?} }}", node.metadata),
+        });
+        write!(f, "{}", debug)
+    }
+}
+
+impl FromStr for Node {
+    type Err = ParseTreeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Node::from_numbers(&read_data(s)?)
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        // trees with the same child counts in pre-order share their shape
+        let mut nodes = self.pre_order();
+        let mut other_nodes = other.pre_order();
+        loop {
+            match (nodes.next(), other_nodes.next()) {
+                (Some(a), Some(b)) => {
+                    if a.children.len() != b.children.len() || a.metadata != b.metadata {
+                        return false;
+                    }
+                }
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl Eq for Node {}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        // avoid recursive drops for deeply nested trees
+        let mut nodes = std::mem::take(&mut self.children);
+        while let Some(mut node) = nodes.pop() {
+            nodes.append(&mut node.children);
+        }
+    }
+}
+
+/// The value of every node in a tree, computed once bottom-up so children
+/// referred to many times are not evaluated again. Values grow exponentially
+/// with the depth of such references and saturate at `u64::MAX`.
+pub struct NodeValues<'a> {
+    root: &'a Node,
+    // values in post-order, so the root's comes last
+    values: Vec<u64>,
+    // positions in `values` of the children of each node
+    children: Vec<Vec<usize>>,
+}
+
+impl<'a> NodeValues<'a> {
+    pub fn new(root: &'a Node) -> Self {
+        let mut values = Vec::new();
+        let mut children = Vec::new();
+        let mut stack: Vec<Vec<usize>> = Vec::new();
+        root.walk(|visit| match visit {
+            Visit::Enter(_, _) => stack.push(Vec::new()),
+            Visit::Leave(node) => {
+                let positions = stack.pop().unwrap();
+                let value = if positions.is_empty() {
+                    saturating_sum(node.metadata.iter().map(|&metadata| u64::from(metadata)))
                 } else {
-                    acc
+                    saturating_sum(
+                        node.metadata
+                            .iter()
+                            .filter_map(|index| index.checked_sub(1))
+                            .filter_map(|index| positions.get(index as usize))
+                            .map(|&position| values[position]),
+                    )
+                };
+                values.push(value);
+                children.push(positions);
+                if let Some(parent) = stack.last_mut() {
+                    parent.push(values.len() - 1);
+                }
+            }
+        });
+        NodeValues {
+            root,
+            values,
+            children,
+        }
+    }
+
+    fn root_position(&self) -> usize {
+        self.values.len() - 1
+    }
+
+    pub fn root_value(&self) -> u64 {
+        self.values[self.root_position()]
+    }
+
+    /// The value of the node reached by following child indices (starting
+    /// at 0) from the root.
+    pub fn value_at(&self, path: &[usize]) -> Option<u64> {
+        let mut position = self.root_position();
+        for index in path {
+            position = *self.children[position].get(*index)?;
+        }
+        Some(self.values[position])
+    }
+
+    /// Follows from the root the child adding the most to the value of its
+    /// parent, the first one on ties, until no child adds anything.
+    pub fn max_value_path(&self) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut node = self.root;
+        let mut position = self.root_position();
+        loop {
+            let best = (0..node.children.len())
+                .map(|index| (self.contribution(node, position, index), Reverse(index)))
+                .max();
+            match best {
+                Some((contribution, Reverse(index))) if contribution != 0 => {
+                    path.push(index);
+                    node = &node.children[index];
+                    position = self.children[position][index];
+                }
+                _ => return path,
+            }
+        }
+    }
+
+    fn contribution(&self, node: &Node, position: usize, index: usize) -> u64 {
+        let references = node
+            .metadata
+            .iter()
+            .filter(|&&metadata| metadata as usize == index + 1)
+            .count() as u64;
+        references.saturating_mul(self.values[self.children[position][index]])
+    }
+
+    /// Paths to all nodes with a value of zero, in pre-order.
+    pub fn zero_value_paths(&self) -> Vec<Vec<usize>> {
+        let mut paths = Vec::new();
+        let mut path = Vec::new();
+        let mut positions: Vec<usize> = Vec::new();
+        self.root.walk(|visit| match visit {
+            Visit::Enter(_, index) => {
+                let position = match positions.last() {
+                    Some(&parent) => {
+                        path.push(index);
+                        self.children[parent][index]
+                    }
+                    None => self.root_position(),
+                };
+                if self.values[position] == 0 {
+                    paths.push(path.clone());
                 }
-            })
+                positions.push(position);
+            }
+            Visit::Leave(_) => {
+                positions.pop();
+                path.pop();
+            }
+        });
+        paths
+    }
+}
+
+fn saturating_sum<I: Iterator<Item = u64>>(values: I) -> u64 {
+    values.fold(0, |sum, value| sum.saturating_add(value))
+}
+
+pub struct PreOrder<'a> {
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for PreOrder<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev());
+        Some(node)
+    }
+}
+
+pub struct PostOrder<'a> {
+    stack: Vec<(&'a Node, usize)>,
+}
+
+impl<'a> Iterator for PostOrder<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, next_child) = self.stack.last_mut()?;
+            let node: &'a Node = node;
+            if let Some(child) = node.children.get(*next_child) {
+                *next_child += 1;
+                self.stack.push((child, 0));
+            } else {
+                self.stack.pop();
+                return Some(node);
+            }
         }
     }
 }
 
-fn read_data(data: &str) -> Vec<u32> {
+pub struct ByDepth<'a> {
+    queue: VecDeque<(usize, &'a Node)>,
+}
+
+impl<'a> Iterator for ByDepth<'a> {
+    type Item = (usize, &'a Node);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (depth, node) = self.queue.pop_front()?;
+        self.queue
+            .extend(node.children.iter().map(|child| (depth + 1, child)));
+        Some((depth, node))
+    }
+}
+
+fn read_data(data: &str) -> Result<Vec<u32>, ParseTreeError> {
     data.split_whitespace()
-        .map(|value| value.parse().unwrap())
+        .map(|value| Ok(value.parse()?))
         .collect()
 }
 
-pub fn metadata_sum(data: &str) -> u32 {
-    let data = read_data(data);
-    metadata_sum_helper(0, &Node::new(&data))
+/// Shape of the random trees built by `generate_tree`.
+#[derive(Clone, Debug)]
+pub struct TreeConfig {
+    pub max_depth: usize,
+    pub max_children: usize,
+    pub min_metadata: usize,
+    pub max_metadata: usize,
+    pub max_metadata_value: u32,
+    pub seed: u64,
 }
 
-fn metadata_sum_helper(sum: u32, node: &Node) -> u32 {
-    node.children
-        .iter()
-        .fold(sum + node.metadata.iter().sum::<u32>(), |sum, node| {
-            metadata_sum_helper(sum, node)
+impl Default for TreeConfig {
+    fn default() -> Self {
+        TreeConfig {
+            max_depth: 4,
+            max_children: 4,
+            min_metadata: 1,
+            max_metadata: 5,
+            max_metadata_value: 20,
+            seed: 1,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum TreeConfigError {
+    NoMetadataValues,
+    InvalidMetadataRange {
+        min: usize,
+        max: usize,
+    },
+    /// Child and metadata counts are read back as `u32` header entries.
+    CountTooLarge(usize),
+}
+
+pub struct GeneratedTree {
+    pub input: String,
+    pub metadata_sum: u64,
+    pub root_value: u64,
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // the all-zero state never changes
+        XorShift(if seed == 0 {
+            0x2545_f491_4f6c_dd1d
+        } else {
+            seed
         })
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn between(&mut self, low: u64, high: u64) -> u64 {
+        low + self.next() % (high - low + 1)
+    }
 }
 
-pub fn root_value(data: &str) -> u32 {
-    Node::new(&read_data(data)).value()
+struct GeneratedNode {
+    depth: usize,
+    children_left: usize,
+    children: Vec<Node>,
+    values: Vec<u64>,
+}
+
+/// Builds a random valid license tree along with the answers for it. Nodes
+/// with children only refer to existing children or to the one right after
+/// the last, never to `0`. Fails if the config leaves no valid metadata.
+pub fn generate_tree(config: &TreeConfig) -> Result<GeneratedTree, TreeConfigError> {
+    if config.max_metadata_value == 0 {
+        return Err(TreeConfigError::NoMetadataValues);
+    }
+    if config.min_metadata > config.max_metadata {
+        return Err(TreeConfigError::InvalidMetadataRange {
+            min: config.min_metadata,
+            max: config.max_metadata,
+        });
+    }
+
+    if let Some(&count) = [config.max_children, config.max_metadata]
+        .iter()
+        .find(|&&count| count > u32::MAX as usize)
+    {
+        return Err(TreeConfigError::CountTooLarge(count));
+    }
+
+    let mut rng = XorShift::new(config.seed);
+    let mut metadata_sum = 0;
+    let mut stack: Vec<GeneratedNode> = Vec::new();
+
+    loop {
+        let depth = stack.last().map_or(0, |parent| parent.depth + 1);
+        let children_len = if depth < config.max_depth {
+            rng.between(0, config.max_children as u64) as usize
+        } else {
+            0
+        };
+        stack.push(GeneratedNode {
+            depth,
+            children_left: children_len,
+            children: Vec::with_capacity(children_len),
+            values: Vec::with_capacity(children_len),
+        });
+
+        loop {
+            let partial = stack.last_mut().unwrap();
+            if partial.children_left != 0 {
+                partial.children_left -= 1;
+                break;
+            }
+
+            let metadata_len =
+                rng.between(config.min_metadata as u64, config.max_metadata as u64) as usize;
+            let highest = if partial.children.is_empty() {
+                config.max_metadata_value
+            } else {
+                partial.children.len() as u32 + 1
+            };
+            let metadata = (0..metadata_len)
+                .map(|_| rng.between(1, highest as u64) as u32)
+                .collect::<Vec<_>>();
+            let metadata_values = metadata.iter().map(|&metadata| u64::from(metadata));
+            metadata_sum += metadata_values.clone().sum::<u64>();
+            let value = if partial.children.is_empty() {
+                saturating_sum(metadata_values)
+            } else {
+                saturating_sum(
+                    metadata
+                        .iter()
+                        .filter_map(|index| partial.values.get(*index as usize - 1).cloned()),
+                )
+            };
+
+            let partial = stack.pop().unwrap();
+            let node = Node::new(partial.children, metadata);
+            match stack.last_mut() {
+                Some(parent) => {
+                    parent.children.push(node);
+                    parent.values.push(value);
+                }
+                None => {
+                    return Ok(GeneratedTree {
+                        input: node.to_string(),
+                        metadata_sum,
+                        root_value: value,
+                    })
+                }
+            }
+        }
+    }
+}
+
+pub fn metadata_sum(data: &str) -> Result<u64, ParseTreeError> {
+    Ok(data.parse::<Node>()?.metadata_sum())
+}
+
+pub fn root_value(data: &str) -> Result<u64, ParseTreeError> {
+    Ok(data.parse::<Node>()?.value())
 }
 
 #[cfg(test)]
@@ -72,25 +611,296 @@ mod tests {
     #[test]
     fn test_read_data() {
         assert_eq!(
-            vec![2, 3, 0, 3, 10, 11, 12, 1, 1, 0, 1, 99, 2, 1, 1, 2],
+            Ok(vec![2, 3, 0, 3, 10, 11, 12, 1, 1, 0, 1, 99, 2, 1, 1, 2]),
             read_data(DATA)
         );
+        assert_eq!(Err(ParseTreeError::InvalidNumber), read_data("2 3 x"));
+        assert_eq!(Err(ParseTreeError::InvalidNumber), read_data("2 -3"));
     }
 
     #[test]
     fn test_node_new() {
-        let data = read_data(DATA);
-        let (_node, data) = Node::from(&data);
+        let data = read_data(DATA).unwrap();
+        let (_node, data) = Node::from(&data).unwrap();
         assert_eq!(0, data.len());
     }
 
+    #[test]
+    fn test_node_errors() {
+        let parse = |data: &str| data.parse::<Node>().map(|_| ());
+        assert_eq!(Err(ParseTreeError::Truncated), parse(""));
+        assert_eq!(Err(ParseTreeError::Truncated), parse("1"));
+        assert_eq!(Err(ParseTreeError::Truncated), parse("0 3 1 2"));
+        assert_eq!(Err(ParseTreeError::Truncated), parse("2 1 0 1 5 1"));
+        assert_eq!(Err(ParseTreeError::TrailingData(2)), parse("0 1 5 1 2"));
+        assert_eq!(
+            Err(ParseTreeError::InvalidMetadataIndex(0)),
+            parse("1 2 0 1 5 1 0")
+        );
+        assert_eq!(Ok(()), parse("1 2 0 1 0 1 3"));
+    }
+
+    #[test]
+    fn test_node_deeply_nested() {
+        let depth = 100_000;
+        let data = format!("{}0 1 7{}", "1 1 ".repeat(depth), " 1".repeat(depth));
+        let data = read_data(&data).unwrap();
+        let node = Node::from_numbers(&data).unwrap();
+        assert_eq!(1, node.children.len());
+        assert_eq!(&[1], node.metadata());
+        assert_eq!(7 + depth as u64, node.metadata_sum());
+        assert_eq!(7, node.value());
+        assert_eq!(depth + 1, node.post_order().count());
+        assert_eq!(depth, node.by_depth().last().unwrap().0);
+        assert_eq!(data.len(), node.to_string().split(' ').count());
+        assert!(format!("{:?}", node).ends_with("metadata: [1] }"));
+    }
+
+    fn leaf(metadata: Vec<u32>) -> Node {
+        Node::new(vec![], metadata)
+    }
+
+    #[test]
+    fn test_node_accessors() {
+        let node = DATA.parse::<Node>().unwrap();
+        assert_eq!(&[1, 1, 2], node.metadata());
+        assert_eq!(2, node.children().len());
+        assert_eq!(&leaf(vec![10, 11, 12]), &node.children()[0]);
+        assert_eq!(
+            Node::new(
+                vec![
+                    leaf(vec![10, 11, 12]),
+                    Node::new(vec![leaf(vec![99])], vec![2])
+                ],
+                vec![1, 1, 2]
+            ),
+            node
+        );
+    }
+
+    #[test]
+    fn test_traversals() {
+        let node = DATA.parse::<Node>().unwrap();
+        let first_metadata = |nodes: Vec<&Node>| {
+            nodes
+                .iter()
+                .map(|node| node.metadata()[0])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![1, 10, 2, 99],
+            first_metadata(node.pre_order().collect())
+        );
+        assert_eq!(
+            vec![10, 99, 2, 1],
+            first_metadata(node.post_order().collect())
+        );
+        assert_eq!(
+            vec![(0, 1), (1, 10), (1, 2), (2, 99)],
+            node.by_depth()
+                .map(|(depth, node)| (depth, node.metadata()[0]))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut node = DATA.parse::<Node>().unwrap();
+        assert_eq!(DATA, node.to_string());
+        assert_eq!(Ok(&node), node.to_string().parse::<Node>().as_ref());
+        assert_eq!("0 0", Node::default().to_string());
+
+        node.children_mut().push(leaf(vec![]));
+        node.metadata_mut().push(3);
+        assert_eq!(
+            "3 4 0 3 10 11 12 1 1 0 1 99 2 0 0 1 1 2 3",
+            node.to_string()
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let node = DATA.parse::<Node>().unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"metadata":[1,1,2],"children":["#,
+                r#"{"metadata":[10,11,12],"children":[]},"#,
+                r#"{"metadata":[2],"children":[{"metadata":[99],"children":[]}]}"#,
+                r#"]}"#
+            ),
+            node.to_json()
+        );
+    }
+
+    #[test]
+    fn test_debug() {
+        let node = DATA.parse::<Node>().unwrap();
+        assert_eq!(
+            concat!(
+                "Node { children: [",
+                "Node { children: [], metadata: [10, 11, 12] }, ",
+                "Node { children: [Node { children: [], metadata: [99] }], metadata: [2] }",
+                "], metadata: [1, 1, 2] }"
+            ),
+            format!("{:?}", node)
+        );
+    }
+
     #[test]
     fn test_metadata_sum() {
-        assert_eq!(138, metadata_sum(DATA));
+        assert_eq!(Ok(138), metadata_sum(DATA));
+        assert_eq!(
+            Err(ParseTreeError::Truncated),
+            metadata_sum("2 3 0 3 10 11")
+        );
     }
 
     #[test]
     fn test_node_value() {
-        assert_eq!(66, root_value(DATA));
+        assert_eq!(Ok(66), root_value(DATA));
+        assert_eq!(
+            Err(ParseTreeError::InvalidMetadataIndex(0)),
+            root_value("1 1 0 1 5 0")
+        );
+    }
+
+    #[test]
+    fn test_generate_tree() {
+        for seed in 0..50 {
+            let config = TreeConfig {
+                seed,
+                ..TreeConfig::default()
+            };
+            let tree = generate_tree(&config).unwrap();
+            assert_eq!(Ok(tree.metadata_sum), metadata_sum(&tree.input));
+            assert_eq!(Ok(tree.root_value), root_value(&tree.input));
+
+            let node = tree.input.parse::<Node>().unwrap();
+            assert!(node.by_depth().all(|(depth, _)| depth <= config.max_depth));
+            assert!(node.pre_order().all(|node| {
+                node.children().len() <= config.max_children
+                    && (1..=config.max_metadata).contains(&node.metadata().len())
+            }));
+        }
+    }
+
+    #[test]
+    fn test_generate_tree_is_deterministic() {
+        let config = TreeConfig {
+            max_depth: 0,
+            seed: 7,
+            ..TreeConfig::default()
+        };
+        let tree = generate_tree(&config).unwrap();
+        assert_eq!(tree.input, generate_tree(&config).unwrap().input);
+        assert_eq!(tree.metadata_sum, tree.root_value);
+        assert!(tree.input.starts_with("0 "));
+    }
+
+    #[test]
+    fn test_generate_tree_invalid_config() {
+        let config = TreeConfig {
+            max_metadata_value: 0,
+            ..TreeConfig::default()
+        };
+        assert_eq!(
+            Err(TreeConfigError::NoMetadataValues),
+            generate_tree(&config).map(|tree| tree.input)
+        );
+
+        let config = TreeConfig {
+            min_metadata: 6,
+            max_metadata: 5,
+            ..TreeConfig::default()
+        };
+        assert_eq!(
+            Err(TreeConfigError::InvalidMetadataRange { min: 6, max: 5 }),
+            generate_tree(&config).map(|tree| tree.input)
+        );
+
+        let config = TreeConfig {
+            max_children: usize::MAX,
+            ..TreeConfig::default()
+        };
+        assert_eq!(
+            Err(TreeConfigError::CountTooLarge(usize::MAX)),
+            generate_tree(&config).map(|tree| tree.input)
+        );
+
+        let config = TreeConfig {
+            max_metadata: usize::MAX,
+            ..TreeConfig::default()
+        };
+        assert_eq!(
+            Err(TreeConfigError::CountTooLarge(usize::MAX)),
+            generate_tree(&config).map(|tree| tree.input)
+        );
+    }
+
+    #[test]
+    fn test_generate_large_tree() {
+        let config = TreeConfig {
+            max_depth: 8,
+            max_children: 6,
+            seed: 2018,
+            ..TreeConfig::default()
+        };
+        let tree = generate_tree(&config).unwrap();
+        let node = tree.input.parse::<Node>().unwrap();
+        assert!(node.pre_order().count() > 1000);
+        assert_eq!(tree.metadata_sum, node.metadata_sum());
+        assert_eq!(tree.root_value, node.value());
+    }
+
+    #[test]
+    fn test_node_values() {
+        let node = DATA.parse::<Node>().unwrap();
+        let values = NodeValues::new(&node);
+        assert_eq!(66, values.root_value());
+        assert_eq!(Some(66), values.value_at(&[]));
+        assert_eq!(Some(33), values.value_at(&[0]));
+        assert_eq!(Some(0), values.value_at(&[1]));
+        assert_eq!(Some(99), values.value_at(&[1, 0]));
+        assert_eq!(None, values.value_at(&[2]));
+        assert_eq!(None, values.value_at(&[1, 1]));
+        assert_eq!(vec![0], values.max_value_path());
+        assert_eq!(vec![vec![1]], values.zero_value_paths());
+    }
+
+    #[test]
+    fn test_node_values_paths() {
+        let node = "2 2 1 1 0 0 3 0 1 9 2 2".parse::<Node>().unwrap();
+        let values = NodeValues::new(&node);
+        assert_eq!(Some(9), values.value_at(&[1]));
+        assert_eq!(18, values.root_value());
+        assert_eq!(vec![1], values.max_value_path());
+        assert_eq!(vec![vec![0], vec![0, 0]], values.zero_value_paths());
+
+        let node = "0 0".parse::<Node>().unwrap();
+        let values = NodeValues::new(&node);
+        assert_eq!(Vec::<usize>::new(), values.max_value_path());
+        assert_eq!(vec![Vec::<usize>::new()], values.zero_value_paths());
+    }
+
+    #[test]
+    fn test_node_value_repeated_references() {
+        // every level refers to its only child three times
+        let depth = 19;
+        let data = format!("{}0 1 1{}", "1 3 ".repeat(depth), " 1 1 1".repeat(depth));
+        assert_eq!(Ok(3u64.pow(depth as u32)), root_value(&data));
+
+        // past the u32 range
+        let depth = 21;
+        let data = format!("{}0 1 1{}", "1 3 ".repeat(depth), " 1 1 1".repeat(depth));
+        assert_eq!(Ok(10_460_353_203), root_value(&data));
+
+        // saturates past the u64 range
+        let depth = 41;
+        let data = format!("{}0 1 1{}", "1 3 ".repeat(depth), " 1 1 1".repeat(depth));
+        let node = data.parse::<Node>().unwrap();
+        let values = NodeValues::new(&node);
+        assert_eq!(u64::MAX, values.root_value());
+        assert_eq!(Some(3u64.pow(40)), values.value_at(&[0]));
+        assert_eq!(vec![0; 41], values.max_value_path());
     }
 }
@@ -1,41 +1,158 @@
-pub fn max_score(players: u32, max_value: u32) -> u32 {
-    *MarbleGame::new(players as usize, max_value)
+use regex::Regex;
+use std::collections::VecDeque;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+#[derive(PartialEq, Debug)]
+pub struct GameDescription {
+    players: u32,
+    last_marble: u32,
+}
+
+impl GameDescription {
+    pub fn new(players: u32, last_marble: u32) -> GameDescription {
+        GameDescription {
+            players,
+            last_marble,
+        }
+    }
+
+    pub fn players(&self) -> u32 {
+        self.players
+    }
+
+    pub fn last_marble(&self) -> u32 {
+        self.last_marble
+    }
+
+    /// The winning score with the last marble multiplied, by 1 for part one
+    /// and 100 for part two.
+    pub fn max_score(&self, last_marble_multiplier: u32) -> Result<u64, MarbleGameError> {
+        let last_marble = self
+            .last_marble
+            .checked_mul(last_marble_multiplier)
+            .ok_or(MarbleGameError::TooManyMarbles)?;
+        max_score(self.players, last_marble)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseGameError();
+
+impl From<ParseIntError> for ParseGameError {
+    fn from(_error: ParseIntError) -> Self {
+        ParseGameError()
+    }
+}
+
+impl FromStr for GameDescription {
+    type Err = ParseGameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // 424 players; last marble is worth 71144 points
+        lazy_static! {
+            static ref RE: Regex =
+                Regex::new(r"^(\d+) players?; last marble is worth (\d+) points?$").unwrap();
+        }
+
+        let caps = RE.captures(s.trim()).ok_or(ParseGameError())?;
+        let players = caps[1].parse::<u32>()?;
+        let last_marble = caps[2].parse::<u32>()?;
+        if players == 0 {
+            return Err(ParseGameError());
+        }
+
+        Ok(GameDescription {
+            players,
+            last_marble,
+        })
+    }
+}
+
+/// How marbles are placed and scored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarbleRules {
+    /// Marbles with a value that is a multiple of this one are kept and score.
+    pub scoring_multiple: u32,
+    /// Counter-clockwise distance of the marble removed when scoring.
+    pub removal_offset: usize,
+    /// Other marbles are placed right after the marble this far clockwise.
+    pub insertion_offset: usize,
+}
+
+impl Default for MarbleRules {
+    fn default() -> Self {
+        MarbleRules {
+            scoring_multiple: 23,
+            removal_offset: 7,
+            insertion_offset: 1,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum MarbleGameError {
+    NoPlayers,
+    /// A scoring multiple below 2, with which the circle can run out of
+    /// marbles to remove.
+    InvalidRules,
+    TooManyMarbles,
+}
+
+pub fn max_score(players: u32, max_value: u32) -> Result<u64, MarbleGameError> {
+    max_score_with(players, max_value, &MarbleRules::default())
+}
+
+pub fn max_score_with(
+    players: u32,
+    max_value: u32,
+    rules: &MarbleRules,
+) -> Result<u64, MarbleGameError> {
+    if players == 0 {
+        return Err(MarbleGameError::NoPlayers);
+    }
+    if rules.scoring_multiple < 2 {
+        return Err(MarbleGameError::InvalidRules);
+    }
+    Ok(*MarbleGame::new(players as usize, max_value, rules.clone())
         .play()
         .iter()
         .max()
-        .unwrap()
+        .unwrap())
 }
 
 struct MarbleGame {
-    marbles: Vec<u32>,
-    scores: Vec<u32>,
-    current_marble_index: usize,
+    // clockwise from the marble after the current one, which is at the back
+    marbles: VecDeque<u32>,
+    scores: Vec<u64>,
+    rules: MarbleRules,
     current_player_index: usize,
     current_value: u32,
     max_value: u32,
 }
 
 impl MarbleGame {
-    fn new(player_count: usize, max_value: u32) -> MarbleGame {
-        let capacity = (max_value + 1) - 2 * ((max_value + 1) / 23);
-        let mut marbles = Vec::with_capacity(capacity as usize);
-        marbles.push(0);
+    fn new(player_count: usize, max_value: u32, rules: MarbleRules) -> MarbleGame {
+        let marble_count = u64::from(max_value) + 1;
+        let scoring_count = marble_count / u64::from(rules.scoring_multiple);
+        let capacity = marble_count.saturating_sub(2 * scoring_count);
+        let mut marbles = VecDeque::with_capacity(capacity as usize);
+        marbles.push_back(0);
         let scores = vec![0; player_count];
-        let current_marble_index = 0;
         let current_player_index = 0;
         let current_value = 0;
 
         MarbleGame {
             marbles,
             scores,
-            current_marble_index,
+            rules,
             current_player_index,
             current_value,
             max_value,
         }
     }
 
-    fn play(&mut self) -> &[u32] {
+    fn play(&mut self) -> &[u64] {
         while self.insert_marble() {}
         &self.scores
     }
@@ -46,33 +163,49 @@ impl MarbleGame {
         }
 
         self.current_value += 1;
-        if self.current_value % 23 != 0 {
-            self.current_marble_index = self.next_marble_index();
-            self.marbles
-                .insert(self.current_marble_index, self.current_value);
+        if !self
+            .current_value
+            .is_multiple_of(self.rules.scoring_multiple)
+        {
+            self.rotate_clockwise(self.rules.insertion_offset);
+            self.marbles.push_back(self.current_value);
         } else {
-            self.current_marble_index = self.remove_marble_index();
+            self.rotate_counter_clockwise(self.rules.removal_offset);
+            // with a scoring multiple of at least 2 the circle holds at
+            // least two marbles whenever one is removed
+            let removed = self.marbles.pop_back().unwrap();
             self.scores[self.current_player_index] +=
-                self.current_value + self.marbles.remove(self.current_marble_index);
-            self.current_marble_index %= self.marbles.len();
+                u64::from(self.current_value) + u64::from(removed);
+            self.rotate_clockwise(1);
         }
 
         self.current_player_index = self.next_player_index();
         true
     }
 
-    fn next_marble_index(&self) -> usize {
-        let index = self.current_marble_index + 1;
-        index % self.marbles.len() + 1
+    /// The circle clockwise starting from marble 0.
+    #[cfg(test)]
+    fn marbles(&self) -> Vec<u32> {
+        let start = self.marbles.iter().position(|&value| value == 0).unwrap();
+        let mut marbles: Vec<u32> = self.marbles.iter().cloned().collect();
+        marbles.rotate_left(start);
+        marbles
+    }
+
+    fn rotate_clockwise(&mut self, steps: usize) {
+        if self.marbles.is_empty() {
+            return;
+        }
+        let steps = steps % self.marbles.len();
+        self.marbles.rotate_left(steps);
     }
 
-    fn remove_marble_index(&self) -> usize {
-        let len = self.marbles.len() as isize;
-        let mut index = (self.current_marble_index as isize - 7) % len;
-        if index < 0 {
-            index += len;
+    fn rotate_counter_clockwise(&mut self, steps: usize) {
+        if self.marbles.is_empty() {
+            return;
         }
-        index as usize
+        let steps = steps % self.marbles.len();
+        self.marbles.rotate_right(steps);
     }
 
     fn next_player_index(&self) -> usize {
@@ -87,105 +220,189 @@ mod tests {
 
     #[test]
     fn test_max_score() {
-        assert_eq!(32, max_score(9, 25));
-        assert_eq!(8317, max_score(10, 1618));
-        assert_eq!(146373, max_score(13, 7999));
-        assert_eq!(2764, max_score(17, 1104));
-        assert_eq!(54718, max_score(21, 6111));
-        assert_eq!(37305, max_score(30, 5807));
+        assert_eq!(Ok(32), max_score(9, 25));
+        assert_eq!(Ok(8317), max_score(10, 1618));
+        assert_eq!(Ok(146373), max_score(13, 7999));
+        assert_eq!(Ok(2764), max_score(17, 1104));
+        assert_eq!(Ok(54718), max_score(21, 6111));
+        assert_eq!(Ok(37305), max_score(30, 5807));
+        assert_eq!(Ok(114068300567), max_score(1, 2_000_000));
+        assert_eq!(Err(MarbleGameError::NoPlayers), max_score(0, 25));
+    }
+
+    #[test]
+    fn test_game_description() {
+        let game = "10 players; last marble is worth 1618 points"
+            .parse::<GameDescription>()
+            .unwrap();
+        assert_eq!(GameDescription::new(10, 1618), game);
+        assert_eq!(10, game.players());
+        assert_eq!(1618, game.last_marble());
+        assert_eq!(Ok(8317), game.max_score(1));
+        assert_eq!(max_score(10, 16180), game.max_score(10));
+        assert_eq!(
+            Err(MarbleGameError::TooManyMarbles),
+            GameDescription::new(10, 50_000_000).max_score(100)
+        );
+        assert_eq!(
+            Err(MarbleGameError::NoPlayers),
+            GameDescription::new(0, 25).max_score(1)
+        );
+
+        assert_eq!(
+            Ok(GameDescription::new(1, 1)),
+            "1 player; last marble is worth 1 point\n"
+                .parse::<GameDescription>()
+                .map_err(|_| ())
+        );
+        assert!("0 players; last marble is worth 25 points"
+            .parse::<GameDescription>()
+            .is_err());
+        assert!("9 players; last marble is worth x points"
+            .parse::<GameDescription>()
+            .is_err());
+        assert!("9 players; last marble is worth 99999999999 points"
+            .parse::<GameDescription>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_max_score_with() {
+        let rules = MarbleRules::default();
+        assert_eq!(Ok(8317), max_score_with(10, 1618, &rules));
+
+        let rules = MarbleRules {
+            scoring_multiple: 5,
+            removal_offset: 1,
+            insertion_offset: 0,
+        };
+        let mut game = MarbleGame::new(3, 20, rules.clone());
+        assert_eq!(46, *game.play().iter().max().unwrap());
+        assert_eq!(
+            game.marbles(),
+            vec![0, 1, 2, 4, 6, 7, 9, 11, 12, 14, 16, 17, 19]
+        );
+        assert_eq!(Ok(46), max_score_with(3, 20, &rules));
+
+        let rules = MarbleRules {
+            scoring_multiple: 3,
+            removal_offset: 0,
+            insertion_offset: 2,
+        };
+        let mut game = MarbleGame::new(2, 10, rules);
+        assert_eq!(22, *game.play().iter().max().unwrap());
+        assert_eq!(game.marbles(), vec![0, 7, 4, 10, 1]);
+
+        for scoring_multiple in 0..2 {
+            let rules = MarbleRules {
+                scoring_multiple,
+                ..MarbleRules::default()
+            };
+            assert_eq!(
+                Err(MarbleGameError::InvalidRules),
+                max_score_with(2, 5, &rules)
+            );
+        }
+        assert_eq!(
+            Err(MarbleGameError::NoPlayers),
+            max_score_with(0, 5, &MarbleRules::default())
+        );
     }
 
     #[test]
     fn test_marbles() {
-        let mut game = MarbleGame::new(9, 25);
-        assert_eq!(game.marbles, vec![0]);
+        let mut game = MarbleGame::new(9, 25, MarbleRules::default());
+        assert_eq!(game.marbles(), vec![0]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 1]);
+        assert_eq!(game.marbles(), vec![0, 1]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 2, 1]);
+        assert_eq!(game.marbles(), vec![0, 2, 1]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 2, 1, 3]);
+        assert_eq!(game.marbles(), vec![0, 2, 1, 3]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 4, 2, 1, 3]);
+        assert_eq!(game.marbles(), vec![0, 4, 2, 1, 3]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 4, 2, 5, 1, 3]);
+        assert_eq!(game.marbles(), vec![0, 4, 2, 5, 1, 3]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 4, 2, 5, 1, 6, 3]);
+        assert_eq!(game.marbles(), vec![0, 4, 2, 5, 1, 6, 3]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 4, 2, 5, 1, 6, 3, 7]);
+        assert_eq!(game.marbles(), vec![0, 4, 2, 5, 1, 6, 3, 7]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 8, 4, 2, 5, 1, 6, 3, 7]);
+        assert_eq!(game.marbles(), vec![0, 8, 4, 2, 5, 1, 6, 3, 7]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 8, 4, 9, 2, 5, 1, 6, 3, 7]);
+        assert_eq!(game.marbles(), vec![0, 8, 4, 9, 2, 5, 1, 6, 3, 7]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 8, 4, 9, 2, 10, 5, 1, 6, 3, 7]);
+        assert_eq!(game.marbles(), vec![0, 8, 4, 9, 2, 10, 5, 1, 6, 3, 7]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 8, 4, 9, 2, 10, 5, 11, 1, 6, 3, 7]);
+        assert_eq!(game.marbles(), vec![0, 8, 4, 9, 2, 10, 5, 11, 1, 6, 3, 7]);
         assert!(game.insert_marble());
-        assert_eq!(game.marbles, vec![0, 8, 4, 9, 2, 10, 5, 11, 1, 12, 6, 3, 7]);
+        assert_eq!(
+            game.marbles(),
+            vec![0, 8, 4, 9, 2, 10, 5, 11, 1, 12, 6, 3, 7]
+        );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 8, 4, 9, 2, 10, 5, 11, 1, 12, 6, 13, 3, 7]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 8, 4, 9, 2, 10, 5, 11, 1, 12, 6, 13, 3, 14, 7]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 8, 4, 9, 2, 10, 5, 11, 1, 12, 6, 13, 3, 14, 7, 15]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 16, 8, 4, 9, 2, 10, 5, 11, 1, 12, 6, 13, 3, 14, 7, 15]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 16, 8, 17, 4, 9, 2, 10, 5, 11, 1, 12, 6, 13, 3, 14, 7, 15]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 16, 8, 17, 4, 18, 9, 2, 10, 5, 11, 1, 12, 6, 13, 3, 14, 7, 15]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 16, 8, 17, 4, 18, 9, 19, 2, 10, 5, 11, 1, 12, 6, 13, 3, 14, 7, 15]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 16, 8, 17, 4, 18, 9, 19, 2, 20, 10, 5, 11, 1, 12, 6, 13, 3, 14, 7, 15]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 16, 8, 17, 4, 18, 9, 19, 2, 20, 10, 21, 5, 11, 1, 12, 6, 13, 3, 14, 7, 15]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 16, 8, 17, 4, 18, 9, 19, 2, 20, 10, 21, 5, 22, 11, 1, 12, 6, 13, 3, 14, 7, 15]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 16, 8, 17, 4, 18, 19, 2, 20, 10, 21, 5, 22, 11, 1, 12, 6, 13, 3, 14, 7, 15]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![0, 16, 8, 17, 4, 18, 19, 2, 24, 20, 10, 21, 5, 22, 11, 1, 12, 6, 13, 3, 14, 7, 15]
         );
         assert!(game.insert_marble());
         assert_eq!(
-            game.marbles,
+            game.marbles(),
             vec![
                 0, 16, 8, 17, 4, 18, 19, 2, 24, 20, 25, 10, 21, 5, 22, 11, 1, 12, 6, 13, 3, 14, 7,
                 15